@@ -0,0 +1,16 @@
+// Prints the program's IDL, optionally with the deployed program id:
+// `cargo run --features idl --bin cdll-idl -- [PROGRAM_ID]`
+
+use circular_doubly_ll_solana::idl::idl;
+use solana_program::pubkey::Pubkey;
+
+fn main() {
+    let program_id = std::env::args().nth(1).map(|arg| {
+        arg.parse::<Pubkey>().unwrap_or_else(|e| {
+            eprintln!("Error: invalid program id {arg}: {e}");
+            std::process::exit(1);
+        })
+    });
+    let idl = serde_json::to_string_pretty(&idl(program_id.as_ref())).expect("IDL serializes");
+    println!("{idl}");
+}
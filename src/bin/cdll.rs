@@ -0,0 +1,27 @@
+// Command-line tool for managing Circular Doubly Linked Lists
+
+use circular_doubly_ll_solana::{
+    cli::{run, Cli},
+    client::ListClient,
+};
+use clap::Parser;
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{commitment_config::CommitmentConfig, signature::read_keypair_file};
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+
+    let keypair_path = cli.keypair_path();
+    let payer = read_keypair_file(&keypair_path).unwrap_or_else(|e| {
+        eprintln!("Error: cannot read keypair {}: {e}", keypair_path.display());
+        std::process::exit(1);
+    });
+    let rpc = RpcClient::new_with_commitment(cli.url, CommitmentConfig::confirmed());
+    let mut client = ListClient::new(rpc, cli.program_id, payer);
+
+    if let Err(e) = run(&mut client, cli.command, &mut std::io::stdout()).await {
+        eprintln!("Error: {e}");
+        std::process::exit(1);
+    }
+}
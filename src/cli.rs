@@ -0,0 +1,206 @@
+// Command-line interface of the `cdll` binary
+
+use std::{error::Error, io::Write, path::PathBuf};
+
+use clap::{Parser, Subcommand};
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::{read_keypair_file, Keypair, Signer},
+};
+
+use crate::{
+    client::{ListClient, Rpc},
+    find_node_address, instruction, MAX_NODE_DATA_LEN,
+};
+
+#[derive(Debug, Parser)]
+#[clap(name = "cdll", version, about = "Manage circular doubly linked lists")]
+pub struct Cli {
+    /// RPC URL of the cluster
+    #[clap(long, short = 'u', default_value = "http://127.0.0.1:8899")]
+    pub url: String,
+    /// Keypair that pays fees and rent and acts as the list authority
+    /// [default: ~/.config/solana/id.json]
+    #[clap(long, short = 'k')]
+    pub keypair: Option<PathBuf>,
+    /// Address of the deployed program
+    #[clap(long)]
+    pub program_id: Pubkey,
+    #[clap(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Create a new list and print its address
+    Init {
+        /// Largest payload, in bytes, a node may carry
+        #[clap(long, default_value_t = MAX_NODE_DATA_LEN)]
+        max_data_len: u32,
+        /// Keypair of the new list header [default: a new keypair]
+        #[clap(long)]
+        list_keypair: Option<PathBuf>,
+    },
+    /// Add a node at the back, or the front with --front
+    Push {
+        list: Pubkey,
+        data: String,
+        #[clap(long)]
+        front: bool,
+    },
+    /// Remove the node at the back, or the front with --front, and print its data
+    Pop {
+        list: Pubkey,
+        #[clap(long)]
+        front: bool,
+    },
+    /// Add a node right after ANCHOR
+    InsertAfter {
+        list: Pubkey,
+        anchor: Pubkey,
+        data: String,
+    },
+    /// Remove a node
+    Remove { list: Pubkey, node: Pubkey },
+    /// Print the list from head to tail
+    Show { list: Pubkey },
+    /// Check the list's links, without sending a transaction
+    Verify { list: Pubkey },
+}
+
+impl Cli {
+    /// The `--keypair` path, or the Solana CLI's default keypair.
+    pub fn keypair_path(&self) -> PathBuf {
+        self.keypair.clone().unwrap_or_else(|| {
+            let home = std::env::var_os("HOME").unwrap_or_default();
+            PathBuf::from(home).join(".config/solana/id.json")
+        })
+    }
+}
+
+/// Prints node data as text when it is printable UTF-8, as hex otherwise.
+fn format_data(data: &[u8]) -> String {
+    match std::str::from_utf8(data) {
+        Ok(text) if !text.chars().any(char::is_control) => format!("{text:?}"),
+        _ => data.iter().fold(String::from("0x"), |mut hex, byte| {
+            hex.push_str(&format!("{byte:02x}"));
+            hex
+        }),
+    }
+}
+
+/// Runs `command` with the client's payer as the list authority, writing
+/// its output to `out`.
+pub async fn run<C: Rpc + Send>(
+    client: &mut ListClient<C>,
+    command: Command,
+    out: &mut impl Write,
+) -> Result<(), Box<dyn Error>> {
+    let program_id = *client.program_id();
+    let authority = client.payer().insecure_clone();
+
+    match command {
+        Command::Init {
+            max_data_len,
+            list_keypair,
+        } => {
+            let list_header = match list_keypair {
+                Some(path) => read_keypair_file(path)?,
+                None => Keypair::new(),
+            };
+            client.initialize_list(&list_header, max_data_len).await?;
+            writeln!(out, "{}", list_header.pubkey())?;
+        }
+        Command::Push { list, data, front } => {
+            let header = client.fetch_list(&list).await?;
+            let (new_node, _) = find_node_address(&program_id, &list, header.next_seq);
+            let neighbors = if header.is_empty() {
+                vec![]
+            } else {
+                vec![header.tail, header.head]
+            };
+            let build = if front {
+                instruction::push_front
+            } else {
+                instruction::push_back
+            };
+            let instruction = build(
+                &program_id,
+                &authority.pubkey(),
+                &authority.pubkey(),
+                &list,
+                &new_node,
+                &neighbors,
+                data.into_bytes(),
+            );
+            client.send(&[instruction], &[]).await?;
+            writeln!(out, "{new_node}")?;
+        }
+        Command::Pop { list, front } => {
+            let header = client.fetch_list(&list).await?;
+            if header.is_empty() {
+                return Err(format!("List {list} is empty").into());
+            }
+            let target = if front { header.head } else { header.tail };
+            let node = client.fetch_node(&target).await?;
+            let neighbors = if header.len == 1 {
+                vec![]
+            } else {
+                vec![node.prev, node.next]
+            };
+            let build = if front {
+                instruction::pop_front
+            } else {
+                instruction::pop_back
+            };
+            let instruction = build(
+                &program_id,
+                &authority.pubkey(),
+                &list,
+                &target,
+                &authority.pubkey(),
+                &neighbors,
+            );
+            client.send(&[instruction], &[]).await?;
+            writeln!(out, "{}", format_data(&node.data))?;
+        }
+        Command::InsertAfter { list, anchor, data } => {
+            let header = client.fetch_list(&list).await?;
+            let anchor_node = client.fetch_node(&anchor).await?;
+            let (new_node, _) = find_node_address(&program_id, &list, header.next_seq);
+            let instruction = instruction::insert_after(
+                &program_id,
+                &authority.pubkey(),
+                &authority.pubkey(),
+                &list,
+                &new_node,
+                &anchor,
+                &anchor_node.next,
+                data.into_bytes(),
+            );
+            client.send(&[instruction], &[]).await?;
+            writeln!(out, "{new_node}")?;
+        }
+        Command::Remove { list, node } => {
+            client
+                .remove(&list, &authority, &node, &authority.pubkey())
+                .await?;
+        }
+        Command::Show { list } => {
+            let header = client.fetch_list(&list).await?;
+            writeln!(
+                out,
+                "List {list}: {} nodes, authority {}",
+                header.len, header.authority
+            )?;
+            for (i, (address, node)) in client.iter_nodes(&list).await?.iter().enumerate() {
+                writeln!(out, "{i}: {address} {}", format_data(&node.data))?;
+            }
+        }
+        Command::Verify { list } => {
+            let len = client.verify_list(&list).await?;
+            writeln!(out, "List of {len} nodes verified")?;
+        }
+    }
+    Ok(())
+}
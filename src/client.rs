@@ -0,0 +1,528 @@
+// Async client for reading and mutating lists
+
+use std::collections::{HashMap, HashSet};
+
+use async_trait::async_trait;
+use borsh::BorshDeserialize;
+use solana_banks_client::BanksClient;
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    account::Account,
+    hash::Hash,
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+use thiserror::Error;
+
+use crate::{find_node_address, instruction, ListHeader, Node, NODE_SEED};
+
+/// Most accounts a single `get_multiple_accounts` RPC call accepts.
+pub const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
+/// Errors returned by `ListClient`.
+#[derive(Debug, Error)]
+pub enum ClientError {
+    #[error("Transport error: {0}")]
+    Transport(Box<dyn std::error::Error + Send + Sync>),
+    #[error("Account {0} does not exist")]
+    AccountNotFound(Pubkey),
+    #[error("Account {0} is not a {1}")]
+    InvalidAccount(Pubkey, &'static str),
+    #[error("Node {node} is reached again after {position} nodes")]
+    Cycle { node: Pubkey, position: u64 },
+    #[error("{from} links to {to}, which is not a node of the list")]
+    DanglingPointer { from: Pubkey, to: Pubkey },
+    #[error("List header counts {expected} nodes but the links form a ring of {found}")]
+    LengthMismatch { expected: u64, found: u64 },
+    #[error("{node} links back to {found} instead of {expected}")]
+    BrokenLink {
+        node: Pubkey,
+        expected: Pubkey,
+        found: Pubkey,
+    },
+    #[error("List header {field} is {found} but the links make it {expected}")]
+    HeaderMismatch {
+        field: &'static str,
+        expected: Pubkey,
+        found: Pubkey,
+    },
+}
+
+/// The few RPC calls the client needs, implemented for both the nonblocking
+/// `RpcClient` and `BanksClient`.
+#[async_trait]
+pub trait Rpc {
+    async fn get_account(&mut self, address: &Pubkey) -> Result<Option<Account>, ClientError>;
+
+    /// Fetches up to `MAX_MULTIPLE_ACCOUNTS` accounts, in the order of
+    /// `addresses`.
+    async fn get_multiple_accounts(
+        &mut self,
+        addresses: &[Pubkey],
+    ) -> Result<Vec<Option<Account>>, ClientError>;
+
+    async fn get_latest_blockhash(&mut self) -> Result<Hash, ClientError>;
+
+    /// Sends the transaction and waits until it is confirmed.
+    async fn send_transaction(&mut self, transaction: Transaction) -> Result<(), ClientError>;
+}
+
+#[async_trait]
+impl Rpc for BanksClient {
+    async fn get_account(&mut self, address: &Pubkey) -> Result<Option<Account>, ClientError> {
+        BanksClient::get_account(self, *address)
+            .await
+            .map_err(|e| ClientError::Transport(e.into()))
+    }
+
+    async fn get_multiple_accounts(
+        &mut self,
+        addresses: &[Pubkey],
+    ) -> Result<Vec<Option<Account>>, ClientError> {
+        // The banks server has no batch call, so this only saves round trips
+        // against a real cluster
+        let mut accounts = Vec::with_capacity(addresses.len());
+        for address in addresses {
+            accounts.push(Rpc::get_account(self, address).await?);
+        }
+        Ok(accounts)
+    }
+
+    async fn get_latest_blockhash(&mut self) -> Result<Hash, ClientError> {
+        BanksClient::get_latest_blockhash(self)
+            .await
+            .map_err(|e| ClientError::Transport(e.into()))
+    }
+
+    async fn send_transaction(&mut self, transaction: Transaction) -> Result<(), ClientError> {
+        self.process_transaction(transaction)
+            .await
+            .map_err(|e| ClientError::Transport(e.into()))
+    }
+}
+
+#[async_trait]
+impl Rpc for RpcClient {
+    async fn get_account(&mut self, address: &Pubkey) -> Result<Option<Account>, ClientError> {
+        self.get_account_with_commitment(address, self.commitment())
+            .await
+            .map(|response| response.value)
+            .map_err(|e| ClientError::Transport(e.into()))
+    }
+
+    async fn get_multiple_accounts(
+        &mut self,
+        addresses: &[Pubkey],
+    ) -> Result<Vec<Option<Account>>, ClientError> {
+        self.get_multiple_accounts_with_commitment(addresses, self.commitment())
+            .await
+            .map(|response| response.value)
+            .map_err(|e| ClientError::Transport(e.into()))
+    }
+
+    async fn get_latest_blockhash(&mut self) -> Result<Hash, ClientError> {
+        RpcClient::get_latest_blockhash(self)
+            .await
+            .map_err(|e| ClientError::Transport(e.into()))
+    }
+
+    async fn send_transaction(&mut self, transaction: Transaction) -> Result<(), ClientError> {
+        self.send_and_confirm_transaction(&transaction)
+            .await
+            .map(|_| ())
+            .map_err(|e| ClientError::Transport(e.into()))
+    }
+}
+
+/// Reads and mutates the lists of one program deployment. `payer` pays the
+/// fees and the rent of new nodes.
+pub struct ListClient<C> {
+    rpc: C,
+    program_id: Pubkey,
+    payer: Keypair,
+}
+
+impl<C: Rpc + Send> ListClient<C> {
+    pub fn new(rpc: C, program_id: Pubkey, payer: Keypair) -> Self {
+        Self {
+            rpc,
+            program_id,
+            payer,
+        }
+    }
+
+    pub fn rpc(&mut self) -> &mut C {
+        &mut self.rpc
+    }
+
+    pub fn program_id(&self) -> &Pubkey {
+        &self.program_id
+    }
+
+    pub fn payer(&self) -> &Keypair {
+        &self.payer
+    }
+
+    async fn fetch_data(
+        &mut self,
+        address: &Pubkey,
+        discriminator: [u8; 8],
+        kind: &'static str,
+    ) -> Result<Vec<u8>, ClientError> {
+        let account = self
+            .rpc
+            .get_account(address)
+            .await?
+            .ok_or(ClientError::AccountNotFound(*address))?;
+        if account.owner != self.program_id || !account.data.starts_with(&discriminator) {
+            return Err(ClientError::InvalidAccount(*address, kind));
+        }
+        Ok(account.data)
+    }
+
+    pub async fn fetch_list(&mut self, list_header: &Pubkey) -> Result<ListHeader, ClientError> {
+        let data = self
+            .fetch_data(list_header, ListHeader::DISCRIMINATOR, "list header")
+            .await?;
+        ListHeader::try_from_slice(&data)
+            .map_err(|_| ClientError::InvalidAccount(*list_header, "list header"))
+    }
+
+    pub async fn fetch_node(&mut self, node: &Pubkey) -> Result<Node, ClientError> {
+        let data = self.fetch_data(node, Node::DISCRIMINATOR, "node").await?;
+        Node::try_from_slice(&data).map_err(|_| ClientError::InvalidAccount(*node, "node"))
+    }
+
+    /// Fetches every node of the list, in order from the head.
+    pub async fn iter_nodes(
+        &mut self,
+        list_header: &Pubkey,
+    ) -> Result<Vec<(Pubkey, Node)>, ClientError> {
+        ListIter::forward(self, list_header).try_collect().await
+    }
+
+    /// Checks the list's links the way `VerifyList` does, without sending a
+    /// transaction or being limited by its account count, and returns the
+    /// number of nodes.
+    pub async fn verify_list(&mut self, list_header: &Pubkey) -> Result<u64, ClientError> {
+        let header = self.fetch_list(list_header).await?;
+        let nodes = self.iter_nodes(list_header).await?;
+        let (head, tail) = match (nodes.first(), nodes.last()) {
+            (Some((head, _)), Some((tail, _))) => (*head, *tail),
+            _ => Default::default(),
+        };
+        for (field, expected, found) in [("head", head, header.head), ("tail", tail, header.tail)] {
+            if found != expected {
+                return Err(ClientError::HeaderMismatch {
+                    field,
+                    expected,
+                    found,
+                });
+            }
+        }
+        let mut prev = tail;
+        for (key, node) in &nodes {
+            if node.prev != prev {
+                return Err(ClientError::BrokenLink {
+                    node: *key,
+                    expected: prev,
+                    found: node.prev,
+                });
+            }
+            prev = *key;
+        }
+        Ok(nodes.len() as u64)
+    }
+
+    /// Signs the instructions with the payer and `signers` and sends them in
+    /// one transaction.
+    pub async fn send(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) -> Result<(), ClientError> {
+        let blockhash = self.rpc.get_latest_blockhash().await?;
+        let mut all_signers = vec![&self.payer];
+        all_signers.extend(signers);
+        let mut transaction = Transaction::new_with_payer(instructions, Some(&self.payer.pubkey()));
+        transaction
+            .try_sign(&all_signers, blockhash)
+            .map_err(|e| ClientError::Transport(e.into()))?;
+        self.rpc.send_transaction(transaction).await
+    }
+
+    /// Creates a list at `list_header` owned by the payer.
+    pub async fn initialize_list(
+        &mut self,
+        list_header: &Keypair,
+        max_data_len: u32,
+    ) -> Result<(), ClientError> {
+        let instruction = instruction::initialize_list(
+            &self.program_id,
+            &self.payer.pubkey(),
+            &list_header.pubkey(),
+            max_data_len,
+        );
+        self.send(&[instruction], &[list_header]).await
+    }
+
+    /// Appends `data` to the list and returns the new node's address.
+    pub async fn append(
+        &mut self,
+        list_header: &Pubkey,
+        authority: &Keypair,
+        data: Vec<u8>,
+    ) -> Result<Pubkey, ClientError> {
+        let header = self.fetch_list(list_header).await?;
+        let (new_node, _) = find_node_address(&self.program_id, list_header, header.next_seq);
+        let neighbors = if header.is_empty() {
+            vec![]
+        } else {
+            vec![header.tail, header.head]
+        };
+        let instruction = instruction::add_node(
+            &self.program_id,
+            &authority.pubkey(),
+            &self.payer.pubkey(),
+            list_header,
+            &new_node,
+            &neighbors,
+            data,
+        );
+        self.send(&[instruction], &[authority]).await?;
+        Ok(new_node)
+    }
+
+    /// Unlinks and closes `node`, sending its rent to `rent_receiver`.
+    pub async fn remove(
+        &mut self,
+        list_header: &Pubkey,
+        authority: &Keypair,
+        node: &Pubkey,
+        rent_receiver: &Pubkey,
+    ) -> Result<(), ClientError> {
+        let target = self.fetch_node(node).await?;
+        // A single node links to itself and has no neighbors to pass
+        let neighbors = if target.next == *node {
+            vec![]
+        } else {
+            vec![target.prev, target.next]
+        };
+        let instruction = instruction::remove_node(
+            &self.program_id,
+            &authority.pubkey(),
+            list_header,
+            node,
+            rent_receiver,
+            &neighbors,
+        );
+        self.send(&[instruction], &[authority]).await
+    }
+}
+
+/// Which link a `ListIter` follows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    /// From the head along `next`.
+    Forward,
+    /// From the tail along `prev`.
+    Reverse,
+}
+
+/// Walks a list one node at a time, stopping with an error instead of looping
+/// forever on corrupted links.
+///
+/// Nodes are fetched in batches of up to `MAX_MULTIPLE_ACCOUNTS`: along with
+/// the node it needs, the iterator prefetches the node addresses of the
+/// `seq`s that follow the last node's in the walking direction, which is where
+/// nodes added with `add_node` end up. No `seq` is asked for twice.
+pub struct ListIter<'a, C> {
+    client: &'a mut ListClient<C>,
+    list_header: Pubkey,
+    direction: Direction,
+    header: Option<ListHeader>,
+    current: Pubkey,
+    last: Option<(Pubkey, u64)>,
+    visited: HashSet<Pubkey>,
+    prefetched: HashMap<Pubkey, Node>,
+    // Sequence numbers a batch already asked for or that were walked, so
+    // later batches do not derive their addresses again
+    requested: HashSet<u64>,
+    done: bool,
+}
+
+impl<'a, C: Rpc + Send> ListIter<'a, C> {
+    pub fn new(client: &'a mut ListClient<C>, list_header: &Pubkey, direction: Direction) -> Self {
+        Self {
+            client,
+            list_header: *list_header,
+            direction,
+            header: None,
+            current: Pubkey::default(),
+            last: None,
+            visited: HashSet::new(),
+            prefetched: HashMap::new(),
+            requested: HashSet::new(),
+            done: false,
+        }
+    }
+
+    /// Walks from the head to the tail.
+    pub fn forward(client: &'a mut ListClient<C>, list_header: &Pubkey) -> Self {
+        Self::new(client, list_header, Direction::Forward)
+    }
+
+    /// Walks from the tail to the head.
+    pub fn reverse(client: &'a mut ListClient<C>, list_header: &Pubkey) -> Self {
+        Self::new(client, list_header, Direction::Reverse)
+    }
+
+    /// Returns the next node and its address, or `None` once the walk is back
+    /// where it started. Nothing is returned after an error.
+    pub async fn try_next(&mut self) -> Result<Option<(Pubkey, Node)>, ClientError> {
+        if self.done {
+            return Ok(None);
+        }
+        let result = self.step().await;
+        if !matches!(result, Ok(Some(_))) {
+            self.done = true;
+        }
+        result
+    }
+
+    /// Walks the rest of the list.
+    pub async fn try_collect(mut self) -> Result<Vec<(Pubkey, Node)>, ClientError> {
+        let mut nodes = vec![];
+        while let Some(node) = self.try_next().await? {
+            nodes.push(node);
+        }
+        Ok(nodes)
+    }
+
+    async fn step(&mut self) -> Result<Option<(Pubkey, Node)>, ClientError> {
+        let header = match &self.header {
+            Some(header) => header,
+            None => {
+                let header = self.client.fetch_list(&self.list_header).await?;
+                self.current = match self.direction {
+                    Direction::Forward => header.head,
+                    Direction::Reverse => header.tail,
+                };
+                self.header.insert(header)
+            }
+        };
+        if header.is_empty() {
+            return Ok(None);
+        }
+        let expected = header.len;
+        let start = match self.direction {
+            Direction::Forward => header.head,
+            Direction::Reverse => header.tail,
+        };
+
+        let found = self.visited.len() as u64;
+        if self.current == start && found > 0 {
+            if found != expected {
+                return Err(ClientError::LengthMismatch { expected, found });
+            }
+            return Ok(None);
+        }
+        if self.visited.contains(&self.current) {
+            return Err(ClientError::Cycle {
+                node: self.current,
+                position: found,
+            });
+        }
+
+        let key = self.current;
+        let from = self.last.map_or(self.list_header, |(last, _)| last);
+        let node = self
+            .fetch(key)
+            .await?
+            .ok_or(ClientError::DanglingPointer { from, to: key })?;
+        self.visited.insert(key);
+        self.last = Some((key, node.seq));
+        self.current = match self.direction {
+            Direction::Forward => node.next,
+            Direction::Reverse => node.prev,
+        };
+        Ok(Some((key, node)))
+    }
+
+    /// Returns the node at `key`, or `None` when `key` is not a node of this
+    /// list, fetching it together with the nodes likely to come after it.
+    async fn fetch(&mut self, key: Pubkey) -> Result<Option<Node>, ClientError> {
+        if let Some(node) = self.prefetched.remove(&key) {
+            return Ok(Some(node));
+        }
+
+        let next_seq = self.header.as_ref().map_or(0, |header| header.next_seq);
+        let program_id = *self.client.program_id();
+        let mut guesses = vec![];
+        if let Some((_, last_seq)) = self.last {
+            let seqs: Box<dyn Iterator<Item = u64>> = match self.direction {
+                Direction::Forward => Box::new(last_seq.saturating_add(1)..next_seq),
+                Direction::Reverse => Box::new((0..last_seq.min(next_seq)).rev()),
+            };
+            // Only the sequence numbers right after the last node are scanned,
+            // however many of them are already known
+            guesses = seqs
+                .take(MAX_MULTIPLE_ACCOUNTS - 1)
+                .filter(|seq| !self.requested.contains(seq))
+                .map(|seq| {
+                    (
+                        seq,
+                        find_node_address(&program_id, &self.list_header, seq).0,
+                    )
+                })
+                .filter(|(_, address)| *address != key)
+                .collect();
+        }
+
+        let mut batch = vec![key];
+        batch.extend(guesses.iter().map(|(_, address)| *address));
+        let mut accounts = self
+            .client
+            .rpc
+            .get_multiple_accounts(&batch)
+            .await?
+            .into_iter();
+        let node = accounts
+            .next()
+            .flatten()
+            .and_then(|account| self.decode(&key, account));
+        if let Some(node) = &node {
+            self.requested.insert(node.seq);
+        }
+        for ((seq, address), account) in guesses.into_iter().zip(accounts) {
+            self.requested.insert(seq);
+            if let Some(node) = account.and_then(|account| self.decode(&address, account)) {
+                self.prefetched.insert(address, node);
+            }
+        }
+        Ok(node)
+    }
+
+    /// Decodes `account` as a node, if it is one that belongs to this list.
+    fn decode(&self, address: &Pubkey, account: Account) -> Option<Node> {
+        if account.owner != self.client.program_id
+            || !account.data.starts_with(&Node::DISCRIMINATOR)
+        {
+            return None;
+        }
+        let node = Node::try_from_slice(&account.data).ok()?;
+        let derived = Pubkey::create_program_address(
+            &[
+                NODE_SEED,
+                self.list_header.as_ref(),
+                &node.seq.to_le_bytes(),
+                &[node.bump],
+            ],
+            &self.client.program_id,
+        )
+        .ok()?;
+        (derived == *address).then_some(node)
+    }
+}
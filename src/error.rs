@@ -0,0 +1,84 @@
+// Error types for the Circular Doubly Linked List program
+
+use num_derive::FromPrimitive;
+use solana_program::{
+    decode_error::DecodeError,
+    msg,
+    program_error::{PrintProgramError, ProgramError},
+};
+use thiserror::Error;
+
+/// Errors returned by the program. The discriminants are part of the public
+/// interface (`ProgramError::Custom(code)`), so new variants are only ever
+/// appended.
+#[derive(Clone, Copy, Debug, Eq, Error, FromPrimitive, PartialEq)]
+pub enum CdllError {
+    #[error("List header is already initialized")]
+    ListAlreadyInitialized = 0,
+    #[error("List header is not initialized")]
+    ListNotInitialized = 1,
+    #[error("Account is not owned by this program")]
+    IncorrectOwner = 2,
+    #[error("Required signature is missing")]
+    MissingSigner = 3,
+    #[error("New node account does not match the expected address")]
+    InvalidNodeAddress = 4,
+    #[error("Node does not belong to this list")]
+    NodeNotInList = 5,
+    #[error("Head node account was not supplied")]
+    HeadAccountMissing = 6,
+    #[error("Tail node account was not supplied")]
+    TailAccountMissing = 7,
+    #[error("Previous node account was not supplied")]
+    PrevAccountMissing = 8,
+    #[error("Next node account was not supplied")]
+    NextAccountMissing = 9,
+    #[error("List is empty")]
+    ListEmpty = 10,
+    #[error("List is full")]
+    ListFull = 11,
+    #[error("Node account has been closed")]
+    NodeClosed = 12,
+    #[error("Signer is not the list authority")]
+    InvalidAuthority = 13,
+    #[error("List authority has been renounced")]
+    ListImmutable = 14,
+    #[error("Anchor node account was not supplied")]
+    AnchorAccountMissing = 15,
+    #[error("Neighboring nodes are not linked to each other")]
+    BrokenLink = 16,
+    #[error("Target node account does not match the expected node")]
+    InvalidTargetNode = 17,
+    #[error("Node data exceeds the list's maximum data length")]
+    DataTooLarge = 18,
+    #[error("Node data does not match the expected value")]
+    DataMismatch = 19,
+    #[error("Number of nodes does not match the list length")]
+    LengthMismatch = 20,
+    #[error("First node is not the list head")]
+    HeadMismatch = 21,
+    #[error("Last node is not the list tail")]
+    TailMismatch = 22,
+    #[error("Account is not of the expected type")]
+    InvalidAccountType = 23,
+    #[error("Account layout version is not supported")]
+    UnsupportedVersion = 24,
+}
+
+impl From<CdllError> for ProgramError {
+    fn from(e: CdllError) -> Self {
+        ProgramError::Custom(e as u32)
+    }
+}
+
+impl<T> DecodeError<T> for CdllError {
+    fn type_of() -> &'static str {
+        "CdllError"
+    }
+}
+
+impl PrintProgramError for CdllError {
+    fn print<E>(&self) {
+        msg!("Error: {}", self);
+    }
+}
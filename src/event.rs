@@ -0,0 +1,118 @@
+// Events emitted by the Circular Doubly Linked List program
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{
+    entrypoint::ProgramResult, log::sol_log_data, program_error::ProgramError, pubkey::Pubkey,
+};
+
+/// Prefix the runtime puts in front of `sol_log_data` output in transaction
+/// logs.
+pub const PROGRAM_DATA_LOG_PREFIX: &str = "Program data: ";
+
+/// One event per list mutation, logged with `sol_log_data` as
+/// `Event::DISCRIMINATOR` followed by the Borsh-encoded event.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, Eq, PartialEq)]
+pub enum Event {
+    ListInitialized {
+        list_header: Pubkey,
+        authority: Pubkey,
+        max_data_len: u32,
+    },
+    NodeAdded {
+        list_header: Pubkey,
+        node: Pubkey,
+        seq: u64,
+        prev: Pubkey,
+        next: Pubkey,
+        data: Vec<u8>,
+    },
+    NodeRemoved {
+        list_header: Pubkey,
+        node: Pubkey,
+        seq: u64,
+    },
+    /// Emitted by `UpdateNode` and `UpdateNodeIfEquals`, with the new payload.
+    NodeUpdated {
+        list_header: Pubkey,
+        node: Pubkey,
+        seq: u64,
+        data: Vec<u8>,
+    },
+    /// `new_authority` is `None` when the authority was renounced.
+    AuthorityChanged {
+        list_header: Pubkey,
+        new_authority: Option<Pubkey>,
+    },
+}
+
+impl Event {
+    pub const DISCRIMINATOR: [u8; 8] = *b"cdllevnt";
+
+    /// Encodes the event the way it is passed to `sol_log_data`.
+    pub fn to_log_data(&self) -> Result<Vec<u8>, ProgramError> {
+        let mut buf = Self::DISCRIMINATOR.to_vec();
+        self.serialize(&mut buf)?;
+        Ok(buf)
+    }
+
+    pub(crate) fn emit(&self) -> ProgramResult {
+        sol_log_data(&[&self.to_log_data()?]);
+        Ok(())
+    }
+
+    /// Decodes an event from the bytes passed to `sol_log_data`. Returns
+    /// `None` for data logged by anyone else.
+    pub fn try_from_log_data(data: &[u8]) -> Option<Self> {
+        let event = data.strip_prefix(&Self::DISCRIMINATOR[..])?;
+        Self::try_from_slice(event).ok()
+    }
+
+    /// Decodes an event from a `Program data: ...` transaction log line,
+    /// without knowing which program logged it.
+    #[cfg(any(test, feature = "client"))]
+    fn try_from_log(log: &str) -> Option<Self> {
+        use base64::{engine::general_purpose::STANDARD, Engine};
+
+        let fields = log.strip_prefix(PROGRAM_DATA_LOG_PREFIX)?;
+        let data = STANDARD.decode(fields.split(' ').next()?).ok()?;
+        Self::try_from_log_data(&data)
+    }
+}
+
+/// Collects the events `program_id` logged in a transaction, in order, from
+/// its log messages. Data lines only count while `program_id` is the
+/// innermost program running, so programs it invokes or that invoke it
+/// cannot forge events. A failed transaction has no events.
+#[cfg(any(test, feature = "client"))]
+pub fn decode_events<S: AsRef<str>>(program_id: &Pubkey, logs: &[S]) -> Vec<Event> {
+    let program_id = program_id.to_string();
+    // Programs being run, innermost last, from the runtime's
+    // `Program <id> invoke [n]` and `Program <id> success` lines
+    let mut invoked = vec![];
+    let mut events = vec![];
+    for log in logs {
+        let log = log.as_ref();
+        let mut words = log.split(' ');
+        let runtime_line = match (words.next(), words.next(), words.next()) {
+            // Programs can only write `Program log:`, `Program data:` and
+            // `Program return:` lines themselves
+            (Some("Program"), Some(id), Some(action)) if id.parse::<Pubkey>().is_ok() => {
+                Some((id, action))
+            }
+            _ => None,
+        };
+        match runtime_line {
+            Some((id, "invoke")) => invoked.push(id),
+            Some((_, "success")) => {
+                invoked.pop();
+            }
+            Some((_, "failed:")) => return vec![],
+            Some(_) => {}
+            None if invoked.last() == Some(&program_id.as_str()) => {
+                events.extend(Event::try_from_log(log));
+            }
+            None => {}
+        }
+    }
+    events
+}
@@ -0,0 +1,389 @@
+// Shank-style JSON IDL of the program, printed by the `cdll-idl` binary
+//
+// Instruction discriminants are taken from the Borsh encoding of
+// `InstructionData` and the errors from `CdllError`, so only the account
+// orderings and the field lists have to be kept in sync by hand.
+
+use num_traits::FromPrimitive;
+use serde_json::{json, Value};
+use solana_program::pubkey::Pubkey;
+
+use crate::{error::CdllError, InstructionData};
+
+fn account(name: &str, is_mut: bool, is_signer: bool) -> Value {
+    json!({ "name": name, "isMut": is_mut, "isSigner": is_signer })
+}
+
+fn account_with_docs(name: &str, is_mut: bool, is_signer: bool, docs: &str) -> Value {
+    let mut account = account(name, is_mut, is_signer);
+    account["docs"] = json!([docs]);
+    account
+}
+
+/// A neighbor node, left out when the instruction does not need it.
+fn optional_node(name: &str, docs: &str) -> Value {
+    let mut account = account_with_docs(name, true, false, docs);
+    account["isOptional"] = json!(true);
+    account
+}
+
+fn system_program() -> Value {
+    account("systemProgram", false, false)
+}
+
+fn field(name: &str, ty: Value) -> Value {
+    json!({ "name": name, "type": ty })
+}
+
+fn discriminator() -> Value {
+    field("discriminator", json!({ "array": ["u8", 8] }))
+}
+
+/// Name of the instruction in the IDL. The match is exhaustive so that a new
+/// variant cannot be added without naming it here.
+fn instruction_name(instruction: &InstructionData) -> &'static str {
+    match instruction {
+        InstructionData::InitializeList { .. } => "initializeList",
+        InstructionData::AddNode { .. } => "addNode",
+        InstructionData::RemoveNode { .. } => "removeNode",
+        InstructionData::SetAuthority { .. } => "setAuthority",
+        InstructionData::InsertAfter { .. } => "insertAfter",
+        InstructionData::InsertBefore { .. } => "insertBefore",
+        InstructionData::PushFront { .. } => "pushFront",
+        InstructionData::PushBack { .. } => "pushBack",
+        InstructionData::PopFront => "popFront",
+        InstructionData::PopBack => "popBack",
+        InstructionData::UpdateNode { .. } => "updateNode",
+        InstructionData::UpdateNodeIfEquals { .. } => "updateNodeIfEquals",
+        InstructionData::VerifyList => "verifyList",
+        InstructionData::ClearList { .. } => "clearList",
+    }
+}
+
+/// Describes the instruction `sample` is a value of.
+fn instruction(
+    sample: InstructionData,
+    docs: &str,
+    accounts: Vec<Value>,
+    args: Vec<Value>,
+) -> Value {
+    let discriminant = borsh::to_vec(&sample).expect("instruction data serializes")[0];
+    json!({
+        "name": instruction_name(&sample),
+        "docs": [docs],
+        "accounts": accounts,
+        "args": args,
+        "discriminant": { "type": "u8", "value": discriminant },
+    })
+}
+
+/// Accounts shared by every insert instruction, followed by the two neighbors
+/// of the new node, which are `optional` only for the instructions that also
+/// insert into an empty list.
+fn insert_accounts(prev: (&str, &str), next: (&str, &str), optional: bool) -> Vec<Value> {
+    let neighbor = |(name, docs)| {
+        if optional {
+            optional_node(name, docs)
+        } else {
+            account_with_docs(name, true, false, docs)
+        }
+    };
+    vec![
+        account("authority", false, true),
+        account("payer", true, true),
+        account("listHeader", true, false),
+        account_with_docs(
+            "newNode",
+            true,
+            false,
+            "Node PDA for the list's nextSeq: [\"node\", listHeader, nextSeq as u64 LE]",
+        ),
+        system_program(),
+        neighbor(prev),
+        neighbor(next),
+    ]
+}
+
+/// Accounts shared by every remove instruction.
+fn remove_accounts(target: &str) -> Vec<Value> {
+    vec![
+        account("authority", false, true),
+        account("listHeader", true, false),
+        account(target, true, false),
+        account("rentReceiver", true, false),
+        optional_node(
+            "prev",
+            "Previous node of the target; omitted for the only node",
+        ),
+        optional_node("next", "Next node of the target; omitted for the only node"),
+    ]
+}
+
+fn update_accounts() -> Vec<Value> {
+    vec![
+        account("authority", false, true),
+        account("payer", true, true),
+        account("listHeader", false, false),
+        account("node", true, false),
+        system_program(),
+    ]
+}
+
+fn instructions() -> Vec<Value> {
+    let tail = ("tail", "Current tail; omitted when the list is empty");
+    let head = ("head", "Current head; omitted when the list is empty");
+    let data = || field("data", json!("bytes"));
+    let anchor = || field("anchor", json!("publicKey"));
+    let node = || field("node", json!("publicKey"));
+    let key = Pubkey::default();
+
+    vec![
+        instruction(
+            InstructionData::InitializeList { max_data_len: 0 },
+            "Creates a list owned by the initializer",
+            vec![
+                account("initializer", true, true),
+                account("listHeader", true, true),
+                system_program(),
+            ],
+            vec![field("maxDataLen", json!("u32"))],
+        ),
+        instruction(
+            InstructionData::AddNode { data: vec![] },
+            "Appends a node",
+            insert_accounts(tail, head, true),
+            vec![data()],
+        ),
+        instruction(
+            InstructionData::RemoveNode { target_node: key },
+            "Unlinks and closes a node",
+            remove_accounts("targetNode"),
+            vec![field("targetNode", json!("publicKey"))],
+        ),
+        instruction(
+            InstructionData::SetAuthority {
+                new_authority: None,
+            },
+            "Transfers the list authority, or renounces it when null",
+            vec![
+                account("authority", false, true),
+                account("listHeader", true, false),
+            ],
+            vec![field("newAuthority", json!({ "option": "publicKey" }))],
+        ),
+        instruction(
+            InstructionData::InsertAfter {
+                anchor: key,
+                data: vec![],
+            },
+            "Inserts a node right after the anchor",
+            insert_accounts(
+                ("anchor", "The anchor node"),
+                ("anchorNext", "Current next node of the anchor"),
+                false,
+            ),
+            vec![anchor(), data()],
+        ),
+        instruction(
+            InstructionData::InsertBefore {
+                anchor: key,
+                data: vec![],
+            },
+            "Inserts a node right before the anchor",
+            insert_accounts(
+                ("anchorPrev", "Current previous node of the anchor"),
+                ("anchor", "The anchor node"),
+                false,
+            ),
+            vec![anchor(), data()],
+        ),
+        instruction(
+            InstructionData::PushFront { data: vec![] },
+            "Inserts a node before the head and makes it the head",
+            insert_accounts(tail, head, true),
+            vec![data()],
+        ),
+        instruction(
+            InstructionData::PushBack { data: vec![] },
+            "Same as addNode",
+            insert_accounts(tail, head, true),
+            vec![data()],
+        ),
+        instruction(
+            InstructionData::PopFront,
+            "Removes the head and returns its data as return data",
+            remove_accounts("head"),
+            vec![],
+        ),
+        instruction(
+            InstructionData::PopBack,
+            "Removes the tail and returns its data as return data",
+            remove_accounts("tail"),
+            vec![],
+        ),
+        instruction(
+            InstructionData::UpdateNode {
+                node: key,
+                data: vec![],
+            },
+            "Replaces the payload of a node",
+            update_accounts(),
+            vec![node(), data()],
+        ),
+        instruction(
+            InstructionData::UpdateNodeIfEquals {
+                node: key,
+                expected: vec![],
+                new: vec![],
+            },
+            "Replaces the payload of a node if it equals the expected value",
+            update_accounts(),
+            vec![
+                node(),
+                field("expected", json!("bytes")),
+                field("new", json!("bytes")),
+            ],
+        ),
+        instruction(
+            InstructionData::VerifyList,
+            "Checks the list's integrity; takes every node in order from the head as remaining accounts",
+            vec![account("listHeader", false, false)],
+            vec![],
+        ),
+        instruction(
+            InstructionData::ClearList { max_nodes: 0 },
+            "Closes up to maxNodes nodes from the head; takes them in order as remaining accounts, followed by the new head and the tail unless the list ends up empty",
+            vec![
+                account("authority", false, true),
+                account("listHeader", true, false),
+                account("rentReceiver", true, false),
+            ],
+            vec![field("maxNodes", json!("u32"))],
+        ),
+    ]
+}
+
+fn accounts() -> Vec<Value> {
+    let key = || json!("publicKey");
+    vec![
+        json!({
+            "name": "ListHeader",
+            "docs": ["Discriminator \"cdllhead\"; head and tail are the default key while the list is empty"],
+            "type": {
+                "kind": "struct",
+                "fields": [
+                    discriminator(),
+                    field("version", json!("u8")),
+                    field("authority", key()),
+                    field("head", key()),
+                    field("tail", key()),
+                    field("len", json!("u64")),
+                    field("nextSeq", json!("u64")),
+                    field("maxDataLen", json!("u32")),
+                ],
+            },
+        }),
+        json!({
+            "name": "Node",
+            "docs": ["Discriminator \"cdllnode\"; PDA of [\"node\", listHeader, seq as u64 LE]"],
+            "type": {
+                "kind": "struct",
+                "fields": [
+                    discriminator(),
+                    field("version", json!("u8")),
+                    field("prev", key()),
+                    field("next", key()),
+                    field("seq", json!("u64")),
+                    field("bump", json!("u8")),
+                    field("data", json!("bytes")),
+                ],
+            },
+        }),
+    ]
+}
+
+fn types() -> Vec<Value> {
+    let key = || json!("publicKey");
+    vec![json!({
+        "name": "Event",
+        "docs": ["Logged with sol_log_data, prefixed with the discriminator \"cdllevnt\""],
+        "type": {
+            "kind": "enum",
+            "variants": [
+                {
+                    "name": "ListInitialized",
+                    "fields": [
+                        field("listHeader", key()),
+                        field("authority", key()),
+                        field("maxDataLen", json!("u32")),
+                    ],
+                },
+                {
+                    "name": "NodeAdded",
+                    "fields": [
+                        field("listHeader", key()),
+                        field("node", key()),
+                        field("seq", json!("u64")),
+                        field("prev", key()),
+                        field("next", key()),
+                        field("data", json!("bytes")),
+                    ],
+                },
+                {
+                    "name": "NodeRemoved",
+                    "fields": [
+                        field("listHeader", key()),
+                        field("node", key()),
+                        field("seq", json!("u64")),
+                    ],
+                },
+                {
+                    "name": "NodeUpdated",
+                    "fields": [
+                        field("listHeader", key()),
+                        field("node", key()),
+                        field("seq", json!("u64")),
+                        field("data", json!("bytes")),
+                    ],
+                },
+                {
+                    "name": "AuthorityChanged",
+                    "fields": [
+                        field("listHeader", key()),
+                        field("newAuthority", json!({ "option": "publicKey" })),
+                    ],
+                },
+            ],
+        },
+    })]
+}
+
+fn errors() -> Vec<Value> {
+    (0..)
+        .map_while(CdllError::from_u32)
+        .map(|error| {
+            json!({
+                "code": error as u32,
+                "name": format!("{error:?}"),
+                "msg": error.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// The IDL of the program, with `program_id` as its address when given.
+pub fn idl(program_id: Option<&Pubkey>) -> Value {
+    let mut idl = json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "name": env!("CARGO_PKG_NAME"),
+        "instructions": instructions(),
+        "accounts": accounts(),
+        "types": types(),
+        "errors": errors(),
+        "metadata": { "origin": "shank" },
+    });
+    if let Some(program_id) = program_id {
+        idl["metadata"]["address"] = json!(program_id.to_string());
+    }
+    idl
+}
@@ -0,0 +1,353 @@
+// Instruction builders for off-chain clients
+//
+// Each function lays out the accounts exactly as the matching handler in the
+// processor reads them. Neighbor nodes after the fixed accounts are looked up
+// by key, so their order does not matter.
+
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    system_program,
+};
+
+use crate::InstructionData;
+
+fn writable(keys: &[Pubkey]) -> impl Iterator<Item = AccountMeta> + '_ {
+    keys.iter().map(|key| AccountMeta::new(*key, false))
+}
+
+/// Accounts shared by every insert instruction, followed by `neighbors`.
+fn insert_accounts(
+    authority: &Pubkey,
+    payer: &Pubkey,
+    list_header: &Pubkey,
+    new_node: &Pubkey,
+    neighbors: &[Pubkey],
+) -> Vec<AccountMeta> {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*authority, true),
+        AccountMeta::new(*payer, true),
+        AccountMeta::new(*list_header, false),
+        AccountMeta::new(*new_node, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    accounts.extend(writable(neighbors));
+    accounts
+}
+
+/// Accounts shared by every remove instruction, followed by `neighbors`.
+fn remove_accounts(
+    authority: &Pubkey,
+    list_header: &Pubkey,
+    target_node: &Pubkey,
+    rent_receiver: &Pubkey,
+    neighbors: &[Pubkey],
+) -> Vec<AccountMeta> {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*authority, true),
+        AccountMeta::new(*list_header, false),
+        AccountMeta::new(*target_node, false),
+        AccountMeta::new(*rent_receiver, false),
+    ];
+    accounts.extend(writable(neighbors));
+    accounts
+}
+
+/// Creates a new list at `list_header`, paid for and owned by `initializer`.
+/// Both have to sign.
+pub fn initialize_list(
+    program_id: &Pubkey,
+    initializer: &Pubkey,
+    list_header: &Pubkey,
+    max_data_len: u32,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &InstructionData::InitializeList { max_data_len },
+        vec![
+            AccountMeta::new(*initializer, true),
+            AccountMeta::new(*list_header, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+/// Appends a node. `new_node` is the node address for the list's
+/// `next_seq`; `neighbors` are the current tail and head, or empty when the
+/// list is.
+pub fn add_node(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    payer: &Pubkey,
+    list_header: &Pubkey,
+    new_node: &Pubkey,
+    neighbors: &[Pubkey],
+    data: Vec<u8>,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &InstructionData::AddNode { data },
+        insert_accounts(authority, payer, list_header, new_node, neighbors),
+    )
+}
+
+/// Same as `add_node`.
+pub fn push_back(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    payer: &Pubkey,
+    list_header: &Pubkey,
+    new_node: &Pubkey,
+    neighbors: &[Pubkey],
+    data: Vec<u8>,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &InstructionData::PushBack { data },
+        insert_accounts(authority, payer, list_header, new_node, neighbors),
+    )
+}
+
+/// Prepends a node. Takes the same accounts as `add_node`.
+pub fn push_front(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    payer: &Pubkey,
+    list_header: &Pubkey,
+    new_node: &Pubkey,
+    neighbors: &[Pubkey],
+    data: Vec<u8>,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &InstructionData::PushFront { data },
+        insert_accounts(authority, payer, list_header, new_node, neighbors),
+    )
+}
+
+/// Inserts a node between `anchor` and its current next node, `anchor_next`.
+#[allow(clippy::too_many_arguments)]
+pub fn insert_after(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    payer: &Pubkey,
+    list_header: &Pubkey,
+    new_node: &Pubkey,
+    anchor: &Pubkey,
+    anchor_next: &Pubkey,
+    data: Vec<u8>,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &InstructionData::InsertAfter {
+            anchor: *anchor,
+            data,
+        },
+        insert_accounts(
+            authority,
+            payer,
+            list_header,
+            new_node,
+            &[*anchor, *anchor_next],
+        ),
+    )
+}
+
+/// Inserts a node between `anchor` and its current previous node,
+/// `anchor_prev`.
+#[allow(clippy::too_many_arguments)]
+pub fn insert_before(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    payer: &Pubkey,
+    list_header: &Pubkey,
+    new_node: &Pubkey,
+    anchor: &Pubkey,
+    anchor_prev: &Pubkey,
+    data: Vec<u8>,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &InstructionData::InsertBefore {
+            anchor: *anchor,
+            data,
+        },
+        insert_accounts(
+            authority,
+            payer,
+            list_header,
+            new_node,
+            &[*anchor_prev, *anchor],
+        ),
+    )
+}
+
+/// Unlinks and closes `target_node`. `neighbors` are its prev and next, or
+/// empty when it is the only node.
+pub fn remove_node(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    list_header: &Pubkey,
+    target_node: &Pubkey,
+    rent_receiver: &Pubkey,
+    neighbors: &[Pubkey],
+) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &InstructionData::RemoveNode {
+            target_node: *target_node,
+        },
+        remove_accounts(
+            authority,
+            list_header,
+            target_node,
+            rent_receiver,
+            neighbors,
+        ),
+    )
+}
+
+/// Removes the head, whose data is returned as return data. Takes the same
+/// accounts as `remove_node`.
+pub fn pop_front(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    list_header: &Pubkey,
+    head: &Pubkey,
+    rent_receiver: &Pubkey,
+    neighbors: &[Pubkey],
+) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &InstructionData::PopFront,
+        remove_accounts(authority, list_header, head, rent_receiver, neighbors),
+    )
+}
+
+/// Removes the tail, whose data is returned as return data. Takes the same
+/// accounts as `remove_node`.
+pub fn pop_back(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    list_header: &Pubkey,
+    tail: &Pubkey,
+    rent_receiver: &Pubkey,
+    neighbors: &[Pubkey],
+) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &InstructionData::PopBack,
+        remove_accounts(authority, list_header, tail, rent_receiver, neighbors),
+    )
+}
+
+/// Transfers the list authority, or renounces it when `new_authority` is
+/// `None`.
+pub fn set_authority(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    list_header: &Pubkey,
+    new_authority: Option<&Pubkey>,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &InstructionData::SetAuthority {
+            new_authority: new_authority.copied(),
+        },
+        vec![
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new(*list_header, false),
+        ],
+    )
+}
+
+fn update_accounts(
+    authority: &Pubkey,
+    payer: &Pubkey,
+    list_header: &Pubkey,
+    node: &Pubkey,
+) -> Vec<AccountMeta> {
+    vec![
+        AccountMeta::new_readonly(*authority, true),
+        AccountMeta::new(*payer, true),
+        AccountMeta::new_readonly(*list_header, false),
+        AccountMeta::new(*node, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ]
+}
+
+/// Replaces the payload of `node`; `payer` settles the rent difference.
+pub fn update_node(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    payer: &Pubkey,
+    list_header: &Pubkey,
+    node: &Pubkey,
+    data: Vec<u8>,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &InstructionData::UpdateNode { node: *node, data },
+        update_accounts(authority, payer, list_header, node),
+    )
+}
+
+/// Same as `update_node`, but only if the current payload equals `expected`.
+pub fn update_node_if_equals(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    payer: &Pubkey,
+    list_header: &Pubkey,
+    node: &Pubkey,
+    expected: Vec<u8>,
+    new: Vec<u8>,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &InstructionData::UpdateNodeIfEquals {
+            node: *node,
+            expected,
+            new,
+        },
+        update_accounts(authority, payer, list_header, node),
+    )
+}
+
+/// Checks the list's integrity. `nodes` are every node in order from the
+/// head.
+pub fn verify_list(program_id: &Pubkey, list_header: &Pubkey, nodes: &[Pubkey]) -> Instruction {
+    let mut accounts = vec![AccountMeta::new_readonly(*list_header, false)];
+    accounts.extend(
+        nodes
+            .iter()
+            .map(|key| AccountMeta::new_readonly(*key, false)),
+    );
+    Instruction::new_with_borsh(*program_id, &InstructionData::VerifyList, accounts)
+}
+
+/// Closes `nodes`, which have to be the first nodes of the list in order.
+/// Unless that empties the list, `remaining` are the node after them and the
+/// tail.
+pub fn clear_list(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    list_header: &Pubkey,
+    rent_receiver: &Pubkey,
+    nodes: &[Pubkey],
+    remaining: &[Pubkey],
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*authority, true),
+        AccountMeta::new(*list_header, false),
+        AccountMeta::new(*rent_receiver, false),
+    ];
+    accounts.extend(writable(nodes));
+    accounts.extend(writable(remaining));
+    Instruction::new_with_borsh(
+        *program_id,
+        &InstructionData::ClearList {
+            max_nodes: nodes.len() as u32,
+        },
+        accounts,
+    )
+}
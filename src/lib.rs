@@ -1,30 +1,202 @@
 // Circular Doubly Linked List Solana Program
 
+use std::cell::RefMut;
+
 use borsh::{BorshDeserialize, BorshSerialize};
+use bytemuck::{Pod, Zeroable};
+use error::CdllError;
+use event::Event;
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
-    entrypoint,
     entrypoint::ProgramResult,
     msg,
-    // program::invoke,
-    program_error::ProgramError,
+    program::{invoke, invoke_signed, set_return_data, MAX_RETURN_DATA},
+    program_error::{PrintProgramError, ProgramError},
     pubkey::Pubkey,
-    // system_instruction,
-    // sysvar::{rent::Rent, Sysvar},
+    system_instruction, system_program,
+    sysvar::{rent::Rent, Sysvar},
 };
 
+#[cfg(feature = "cli")]
+pub mod cli;
+#[cfg(feature = "client")]
+pub mod client;
+pub mod error;
+pub mod event;
+#[cfg(feature = "idl")]
+pub mod idl;
+pub mod instruction;
+#[cfg(any(test, feature = "model"))]
+pub mod model;
+
+/// Current layout version written into every `ListHeader`.
+pub const LIST_HEADER_VERSION: u8 = 2;
+
+/// Current layout version written into every `Node`.
+pub const NODE_VERSION: u8 = 1;
+
+/// Upper bound for `ListHeader::max_data_len`, so that a popped payload always
+/// fits in return data.
+pub const MAX_NODE_DATA_LEN: u32 = MAX_RETURN_DATA as u32;
+
+/// Seed prefix for node PDAs: `[NODE_SEED, list_header, seq.to_le_bytes()]`.
+pub const NODE_SEED: &[u8] = b"node";
+
 #[derive(BorshSerialize, BorshDeserialize, Debug)]
 pub struct Node {
-    pub data: u64,
+    pub discriminator: [u8; 8],
+    pub version: u8,
     pub prev: Pubkey,
     pub next: Pubkey,
+    pub seq: u64,
+    pub bump: u8,
+    pub data: Vec<u8>,
+}
+
+/// List-wide metadata. `head` and `tail` are `Pubkey::default()` while the
+/// list is empty; `authority` is `Pubkey::default()` once renounced.
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub struct ListHeader {
+    pub discriminator: [u8; 8],
+    pub version: u8,
+    pub authority: Pubkey,
+    pub head: Pubkey,
+    pub tail: Pubkey,
+    pub len: u64,
+    /// Sequence number used to derive the next node address. Never reused.
+    pub next_seq: u64,
+    /// Largest payload, in bytes, a node of this list may carry.
+    pub max_data_len: u32,
+}
+
+impl Node {
+    pub const DISCRIMINATOR: [u8; 8] = *b"cdllnode";
+
+    /// Serialized size of a node with an empty payload: the prefix plus the
+    /// `u32` length of `data`.
+    pub const LEN: usize = NodePrefix::LEN + 4;
+
+    /// Serialized size, and so the account size, of a node carrying
+    /// `data_len` bytes.
+    pub const fn space(data_len: usize) -> usize {
+        Self::LEN + data_len
+    }
+}
+
+/// The fixed-size start of a serialized `Node`, up to (excluding) the
+/// payload. Read and written in place, so relinking a neighbor does not
+/// round-trip its payload through Borsh.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+pub struct NodePrefix {
+    pub discriminator: [u8; 8],
+    pub version: u8,
+    pub prev: Pubkey,
+    pub next: Pubkey,
+    /// `Node::seq`, little-endian.
+    pub seq: [u8; 8],
+    pub bump: u8,
+}
+
+impl NodePrefix {
+    pub const LEN: usize = std::mem::size_of::<Self>();
+
+    pub fn seq(&self) -> u64 {
+        u64::from_le_bytes(self.seq)
+    }
+}
+
+// `#[repr(C)]` only matches the Borsh encoding while every field has an
+// alignment of 1, i.e. no padding is inserted.
+const _: () = assert!(NodePrefix::LEN == 8 + 1 + 32 + 32 + 8 + 1);
+
+impl ListHeader {
+    pub const DISCRIMINATOR: [u8; 8] = *b"cdllhead";
+
+    /// Serialized size, and so the account size, of a list header.
+    pub const LEN: usize = 8 + 1 + 32 + 32 + 32 + 8 + 8 + 4;
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// Derives the address of the node with sequence number `seq` in `list_header`.
+pub fn find_node_address(program_id: &Pubkey, list_header: &Pubkey, seq: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[NODE_SEED, list_header.as_ref(), &seq.to_le_bytes()],
+        program_id,
+    )
 }
 
 #[derive(BorshSerialize, BorshDeserialize, Debug)]
 pub enum InstructionData {
-    InitializeList,
-    AddNode { data: u64 },
-    RemoveNode { target_node: Pubkey },
+    InitializeList {
+        max_data_len: u32,
+    },
+    AddNode {
+        data: Vec<u8>,
+    },
+    RemoveNode {
+        target_node: Pubkey,
+    },
+    /// Transfers the list authority, or renounces it when `None`, which makes
+    /// the list immutable.
+    SetAuthority {
+        new_authority: Option<Pubkey>,
+    },
+    /// Inserts a new node right after `anchor`. Takes the same accounts as
+    /// `AddNode`, followed by the anchor and its current `next`.
+    InsertAfter {
+        anchor: Pubkey,
+        data: Vec<u8>,
+    },
+    /// Inserts a new node right before `anchor`. Takes the same accounts as
+    /// `AddNode`, followed by the anchor and its current `prev`.
+    InsertBefore {
+        anchor: Pubkey,
+        data: Vec<u8>,
+    },
+    /// Inserts a new node before the head and makes it the head. Takes the
+    /// same accounts as `AddNode`.
+    PushFront {
+        data: Vec<u8>,
+    },
+    /// Same as `AddNode`.
+    PushBack {
+        data: Vec<u8>,
+    },
+    /// Removes the head and returns its data as return data. Takes the same
+    /// accounts as `RemoveNode`, with the head as the target.
+    PopFront,
+    /// Removes the tail and returns its data as return data. Takes the same
+    /// accounts as `RemoveNode`, with the tail as the target.
+    PopBack,
+    /// Replaces the payload of `node`, resizing the account and settling the
+    /// rent difference with the payer.
+    UpdateNode {
+        node: Pubkey,
+        data: Vec<u8>,
+    },
+    /// Same as `UpdateNode`, but only if the current payload equals
+    /// `expected`.
+    UpdateNodeIfEquals {
+        node: Pubkey,
+        expected: Vec<u8>,
+        new: Vec<u8>,
+    },
+    /// Checks the list's integrity. Takes the list header followed by every
+    /// node in order from the head; fails at the first broken link.
+    VerifyList,
+    /// Closes up to `max_nodes` nodes from the head onwards, refunding their
+    /// rent. Takes the authority (signer), the list header (writable), the
+    /// rent receiver (writable) and the nodes to close in order from the
+    /// head, followed by the new head and the tail when the batch does not
+    /// empty the list. Repeat until the list is empty. A `max_nodes` of 0
+    /// does nothing.
+    ClearList {
+        max_nodes: u32,
+    },
 }
 
 impl InstructionData {
@@ -34,7 +206,9 @@ impl InstructionData {
         Ok(buf)
     }
 }
-entrypoint!(process_instruction);
+
+#[cfg(not(feature = "no-entrypoint"))]
+solana_program::entrypoint!(process_instruction);
 
 pub fn process_instruction(
     program_id: &Pubkey,
@@ -43,210 +217,890 @@ pub fn process_instruction(
 ) -> ProgramResult {
     let instruction = InstructionData::try_from_slice(instruction_data)
         .map_err(|_| ProgramError::InvalidInstructionData)?;
-    match instruction {
-        InstructionData::InitializeList => {
+    let result = match instruction {
+        InstructionData::InitializeList { max_data_len } => {
             msg!("Instruction: InitializeList");
-            initialize_list(program_id, accounts)
+            initialize_list(program_id, accounts, max_data_len)
         }
         InstructionData::AddNode { data } => {
             msg!("Instruction: AddNode");
-            add_node(program_id, accounts, data)
+            insert_node(program_id, accounts, data, InsertPosition::Back)
         }
         InstructionData::RemoveNode { target_node } => {
             msg!("Instruction: RemoveNode");
-            remove_node(program_id, accounts, target_node)
+            remove_node(program_id, accounts, RemoveTarget::Key(target_node)).map(|_| ())
+        }
+        InstructionData::SetAuthority { new_authority } => {
+            msg!("Instruction: SetAuthority");
+            set_authority(program_id, accounts, new_authority)
+        }
+        InstructionData::InsertAfter { anchor, data } => {
+            msg!("Instruction: InsertAfter");
+            insert_node(program_id, accounts, data, InsertPosition::After(anchor))
+        }
+        InstructionData::InsertBefore { anchor, data } => {
+            msg!("Instruction: InsertBefore");
+            insert_node(program_id, accounts, data, InsertPosition::Before(anchor))
+        }
+        InstructionData::PushFront { data } => {
+            msg!("Instruction: PushFront");
+            insert_node(program_id, accounts, data, InsertPosition::Front)
+        }
+        InstructionData::PushBack { data } => {
+            msg!("Instruction: PushBack");
+            insert_node(program_id, accounts, data, InsertPosition::Back)
+        }
+        InstructionData::PopFront => {
+            msg!("Instruction: PopFront");
+            pop_node(program_id, accounts, RemoveTarget::Front)
         }
+        InstructionData::PopBack => {
+            msg!("Instruction: PopBack");
+            pop_node(program_id, accounts, RemoveTarget::Back)
+        }
+        InstructionData::UpdateNode { node, data } => {
+            msg!("Instruction: UpdateNode");
+            update_node(program_id, accounts, node, None, data)
+        }
+        InstructionData::UpdateNodeIfEquals {
+            node,
+            expected,
+            new,
+        } => {
+            msg!("Instruction: UpdateNodeIfEquals");
+            update_node(program_id, accounts, node, Some(expected), new)
+        }
+        InstructionData::VerifyList => {
+            msg!("Instruction: VerifyList");
+            verify_list(program_id, accounts)
+        }
+        InstructionData::ClearList { max_nodes } => {
+            msg!("Instruction: ClearList");
+            clear_list(program_id, accounts, max_nodes)
+        }
+    };
+
+    if let Err(error) = &result {
+        error.print::<CdllError>();
+    }
+    result
+}
+
+/// Checks the discriminator and layout version every account starts with.
+fn check_account_type(data: &[u8], discriminator: [u8; 8], version: u8) -> ProgramResult {
+    if data.get(..8) != Some(&discriminator[..]) {
+        return Err(CdllError::InvalidAccountType.into());
+    }
+    if data.get(8) != Some(&version) {
+        return Err(CdllError::UnsupportedVersion.into());
+    }
+    Ok(())
+}
+
+fn load_list_header(
+    program_id: &Pubkey,
+    account: &AccountInfo,
+) -> Result<ListHeader, ProgramError> {
+    if account.owner != program_id {
+        return Err(CdllError::IncorrectOwner.into());
+    }
+    if account.data_is_empty() {
+        return Err(CdllError::ListNotInitialized.into());
+    }
+    let data = account.data.borrow();
+    check_account_type(&data, ListHeader::DISCRIMINATOR, LIST_HEADER_VERSION)?;
+    let header = ListHeader::try_from_slice(&data).map_err(|_| CdllError::ListNotInitialized)?;
+    Ok(header)
+}
+
+fn store_list_header(header: &ListHeader, account: &AccountInfo) -> ProgramResult {
+    header.serialize(&mut &mut account.data.borrow_mut()[..])?;
+    Ok(())
+}
+
+fn check_data_len(header: &ListHeader, data: &[u8]) -> ProgramResult {
+    if data.len() > header.max_data_len as usize {
+        return Err(CdllError::DataTooLarge.into());
+    }
+    Ok(())
+}
+
+/// Checks that `authority` signed and is the list's current authority.
+fn check_authority(header: &ListHeader, authority: &AccountInfo) -> ProgramResult {
+    if header.authority == Pubkey::default() {
+        return Err(CdllError::ListImmutable.into());
+    }
+    if !authority.is_signer {
+        return Err(CdllError::MissingSigner.into());
+    }
+    if *authority.key != header.authority {
+        return Err(CdllError::InvalidAuthority.into());
+    }
+    Ok(())
+}
+
+/// Checks that `account` is a live node of the list and copies out its
+/// prefix, without deserializing the payload.
+fn load_node_prefix(
+    program_id: &Pubkey,
+    list_header_key: &Pubkey,
+    account: &AccountInfo,
+) -> Result<NodePrefix, ProgramError> {
+    if account.owner == &system_program::ID && account.data_is_empty() {
+        return Err(CdllError::NodeClosed.into());
+    }
+    if account.owner != program_id {
+        return Err(CdllError::IncorrectOwner.into());
+    }
+    let data = account.data.borrow();
+    check_account_type(&data, Node::DISCRIMINATOR, NODE_VERSION)?;
+    let prefix: NodePrefix = data
+        .get(..NodePrefix::LEN)
+        .map(bytemuck::pod_read_unaligned)
+        .ok_or(ProgramError::InvalidAccountData)?;
+    let expected_key = Pubkey::create_program_address(
+        &[
+            NODE_SEED,
+            list_header_key.as_ref(),
+            &prefix.seq,
+            &[prefix.bump],
+        ],
+        program_id,
+    )
+    .map_err(|_| CdllError::NodeNotInList)?;
+    if expected_key != *account.key {
+        return Err(CdllError::NodeNotInList.into());
+    }
+    Ok(prefix)
+}
+
+/// Same checks as `load_node_prefix`, but borrows the prefix mutably so links
+/// can be updated in place.
+fn load_node_prefix_mut<'a>(
+    program_id: &Pubkey,
+    list_header_key: &Pubkey,
+    account: &'a AccountInfo,
+) -> Result<RefMut<'a, NodePrefix>, ProgramError> {
+    load_node_prefix(program_id, list_header_key, account)?;
+    Ok(RefMut::map(account.data.borrow_mut(), |data| {
+        bytemuck::from_bytes_mut(&mut data[..NodePrefix::LEN])
+    }))
+}
+
+fn load_node(
+    program_id: &Pubkey,
+    list_header_key: &Pubkey,
+    account: &AccountInfo,
+) -> Result<Node, ProgramError> {
+    load_node_prefix(program_id, list_header_key, account)?;
+    Ok(Node::try_from_slice(&account.data.borrow())?)
+}
+
+fn store_node(node: &Node, account: &AccountInfo) -> ProgramResult {
+    node.serialize(&mut &mut account.data.borrow_mut()[..])?;
+    Ok(())
+}
+
+/// Logs what relinking `account` to `new_prev` costs both ways: the full
+/// deserialize and re-serialize relinking used to do, and the in-place
+/// prefix write that replaced it. Only built for
+/// `bench_relink_compute_units`.
+#[cfg(feature = "bench-relink")]
+fn bench_relink(
+    program_id: &Pubkey,
+    list_header_key: &Pubkey,
+    account: &AccountInfo,
+    new_prev: &Pubkey,
+) -> ProgramResult {
+    use solana_program::compute_units::sol_remaining_compute_units;
+
+    let start = sol_remaining_compute_units();
+    let mut node = load_node(program_id, list_header_key, account)?;
+    node.prev = *new_prev;
+    store_node(&node, account)?;
+    let full = start - sol_remaining_compute_units();
+
+    let start = sol_remaining_compute_units();
+    load_node_prefix_mut(program_id, list_header_key, account)?.prev = *new_prev;
+    let in_place = start - sol_remaining_compute_units();
+
+    msg!("Relink: {} CU full, {} CU in place", full, in_place);
+    Ok(())
+}
+
+/// Resizes a program-owned account to `new_size`, topping it up from `payer`
+/// or refunding the excess to `payer` so it stays exactly rent exempt.
+fn resize_account<'a>(
+    account: &AccountInfo<'a>,
+    payer: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    new_size: usize,
+) -> ProgramResult {
+    let required_lamports = Rent::get()?.minimum_balance(new_size);
+    let current_lamports = account.lamports();
+
+    if required_lamports > current_lamports {
+        invoke(
+            &system_instruction::transfer(
+                payer.key,
+                account.key,
+                required_lamports - current_lamports,
+            ),
+            &[payer.clone(), account.clone(), system_program.clone()],
+        )?;
+    } else if current_lamports > required_lamports {
+        **payer.lamports.borrow_mut() = payer
+            .lamports()
+            .checked_add(current_lamports - required_lamports)
+            .ok_or(ProgramError::ArithmeticOverflow)?;
+        **account.lamports.borrow_mut() = required_lamports;
     }
+
+    account.realloc(new_size, false)
 }
 
-fn initialize_list(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
-    msg!("Initializing Circular Doubly Linked List");
+/// Creates the rent-exempt, program-owned account at the PDA signed for by
+/// `seeds`. Node addresses are predictable, so anyone can send lamports to one
+/// before it is created; `create_account` would then fail for good, so such an
+/// account is topped up, allocated and assigned instead.
+fn create_pda_account<'a>(
+    program_id: &Pubkey,
+    payer: &AccountInfo<'a>,
+    account: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    space: usize,
+    seeds: &[&[u8]],
+) -> ProgramResult {
+    let required_lamports = Rent::get()?.minimum_balance(space);
+    let current_lamports = account.lamports();
 
+    if current_lamports == 0 {
+        return invoke_signed(
+            &system_instruction::create_account(
+                payer.key,
+                account.key,
+                required_lamports,
+                space as u64,
+                program_id,
+            ),
+            &[payer.clone(), account.clone(), system_program.clone()],
+            &[seeds],
+        );
+    }
+
+    if required_lamports > current_lamports {
+        invoke(
+            &system_instruction::transfer(
+                payer.key,
+                account.key,
+                required_lamports - current_lamports,
+            ),
+            &[payer.clone(), account.clone(), system_program.clone()],
+        )?;
+    }
+    invoke_signed(
+        &system_instruction::allocate(account.key, space as u64),
+        &[account.clone(), system_program.clone()],
+        &[seeds],
+    )?;
+    invoke_signed(
+        &system_instruction::assign(account.key, program_id),
+        &[account.clone(), system_program.clone()],
+        &[seeds],
+    )
+}
+
+/// Zeroes the node's data, hands the account back to the system program and
+/// moves its lamports to `rent_receiver`.
+fn close_node_account(node_account: &AccountInfo, rent_receiver: &AccountInfo) -> ProgramResult {
+    let lamports = node_account.lamports();
+    **rent_receiver.lamports.borrow_mut() = rent_receiver
+        .lamports()
+        .checked_add(lamports)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+    **node_account.lamports.borrow_mut() = 0;
+
+    node_account.data.borrow_mut().fill(0);
+    node_account.realloc(0, false)?;
+    node_account.assign(&system_program::ID);
+    Ok(())
+}
+
+/// Looks up a node account by key among the instruction's accounts, failing
+/// with `missing` when the client did not supply it.
+fn find_account<'a, 'b>(
+    accounts: &'a [AccountInfo<'b>],
+    key: &Pubkey,
+    missing: CdllError,
+) -> Result<&'a AccountInfo<'b>, ProgramError> {
+    accounts
+        .iter()
+        .find(|a| a.key == key)
+        .ok_or_else(|| missing.into())
+}
+
+fn initialize_list(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    max_data_len: u32,
+) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
-    let _initializer = next_account_info(account_info_iter)?;
-    let head_account = next_account_info(account_info_iter)?;
+    let initializer = next_account_info(account_info_iter)?; // Pays for and owns the list
+    let list_header_account = next_account_info(account_info_iter)?; // New list header account
+    let system_program = next_account_info(account_info_iter)?;
 
-    if head_account.owner != program_id {
-        return Err(ProgramError::IncorrectProgramId);
+    if !initializer.is_signer || !list_header_account.is_signer {
+        return Err(CdllError::MissingSigner.into());
     }
 
-    if !head_account.data_is_empty() {
-        if head_account.data.borrow().to_vec() != vec![0_u8; std::mem::size_of::<Node>()] {
-            return Err(ProgramError::AccountAlreadyInitialized);
-        }
+    if !list_header_account.data_is_empty() {
+        return Err(CdllError::ListAlreadyInitialized.into());
     }
 
-    let node = Node {
-        data: 0,
-        prev: *head_account.key,
-        next: *head_account.key,
+    if max_data_len > MAX_NODE_DATA_LEN {
+        return Err(CdllError::DataTooLarge.into());
+    }
+
+    let header = ListHeader {
+        discriminator: ListHeader::DISCRIMINATOR,
+        version: LIST_HEADER_VERSION,
+        authority: *initializer.key,
+        head: Pubkey::default(),
+        tail: Pubkey::default(),
+        len: 0,
+        next_seq: 0,
+        max_data_len,
     };
+    let header_size = ListHeader::LEN;
 
-    node.serialize(&mut &mut head_account.data.borrow_mut()[..])?;
+    let rent = Rent::get()?;
+    invoke(
+        &system_instruction::create_account(
+            initializer.key,
+            list_header_account.key,
+            rent.minimum_balance(header_size),
+            header_size as u64,
+            program_id,
+        ),
+        &[
+            initializer.clone(),
+            list_header_account.clone(),
+            system_program.clone(),
+        ],
+    )?;
 
-    Ok(())
+    store_list_header(&header, list_header_account)?;
+
+    Event::ListInitialized {
+        list_header: *list_header_account.key,
+        authority: header.authority,
+        max_data_len,
+    }
+    .emit()
 }
 
-fn add_node(program_id: &Pubkey, accounts: &[AccountInfo], data: u64) -> ProgramResult {
-    msg!("Adding Node with data: {}", data);
+/// Where `insert_node` splices the new node in.
+enum InsertPosition {
+    /// Between the tail and the head, becoming the new head.
+    Front,
+    /// Between the tail and the head, becoming the new tail.
+    Back,
+    /// Right after the anchor node.
+    After(Pubkey),
+    /// Right before the anchor node.
+    Before(Pubkey),
+}
 
+fn insert_node(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: Vec<u8>,
+    position: InsertPosition,
+) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
-    msg!("1");
-    let head_account = next_account_info(account_info_iter)?; // Head node account
-    msg!("2");
-    let _tail_account = next_account_info(account_info_iter)?; // Account paying for the transaction
-    msg!("3");
-    msg!("head_account_key: {:?}", head_account.key);
-    msg!("head_account: {:?}", head_account);
+    let authority_account = next_account_info(account_info_iter)?; // List authority
+    let payer_account = next_account_info(account_info_iter)?; // Account paying for the new node
+    let list_header_account = next_account_info(account_info_iter)?; // List header account
     let new_node_account = next_account_info(account_info_iter)?; // New node account
-    msg!("4");
+    let system_program = next_account_info(account_info_iter)?;
 
-    if head_account.owner != program_id {
-        return Err(ProgramError::IncorrectProgramId);
+    if !payer_account.is_signer {
+        return Err(CdllError::MissingSigner.into());
     }
 
-    // Calculate the size needed for the Node struct
-    // let node_size = std::mem::size_of::<Node>();
+    let mut header = load_list_header(program_id, list_header_account)?;
+    check_authority(&header, authority_account)?;
+    check_data_len(&header, &data)?;
 
-    // Calculate the minimum lamports required for rent exemption
-    // let rent = Rent::get()?;
-    // let required_lamports = rent.minimum_balance(node_size);
-    msg!("5");
-    let system_program = next_account_info(account_info_iter)?;
-    msg!("system_program: {:?}", system_program);
-    // Create the new node account
-    // invoke(
-    //     &system_instruction::create_account(
-    //         payer_account.key,
-    //         new_node_account.key,
-    //         required_lamports,
-    //         node_size as u64,
-    //         program_id,
-    //     ),
-    //     &[
-    //         payer_account.clone(),
-    //         new_node_account.clone(),
-    //         system_program.clone(),
-    //     ],
-    // )?;
-    msg!("6");
-    // Deserialize the head node without mutably borrowing
-    let head_node = {
-        let head_data = head_account.data.borrow();
-        Node::try_from_slice(&head_data)?
+    let seq = header.next_seq;
+    let (new_node_key, bump) = find_node_address(program_id, list_header_account.key, seq);
+    if new_node_key != *new_node_account.key {
+        return Err(CdllError::InvalidNodeAddress.into());
+    }
+
+    // The nodes the new node is spliced between, and the error to report when
+    // the client did not supply them
+    let neighbors = if header.is_empty() {
+        if !matches!(position, InsertPosition::Front | InsertPosition::Back) {
+            return Err(CdllError::ListEmpty.into());
+        }
+        None
+    } else {
+        Some(match position {
+            InsertPosition::Front | InsertPosition::Back => (
+                header.tail,
+                CdllError::TailAccountMissing,
+                header.head,
+                CdllError::HeadAccountMissing,
+            ),
+            InsertPosition::After(anchor) => {
+                let anchor_account =
+                    find_account(accounts, &anchor, CdllError::AnchorAccountMissing)?;
+                let anchor_node =
+                    load_node_prefix(program_id, list_header_account.key, anchor_account)?;
+                (
+                    anchor,
+                    CdllError::AnchorAccountMissing,
+                    anchor_node.next,
+                    CdllError::NextAccountMissing,
+                )
+            }
+            InsertPosition::Before(anchor) => {
+                let anchor_account =
+                    find_account(accounts, &anchor, CdllError::AnchorAccountMissing)?;
+                let anchor_node =
+                    load_node_prefix(program_id, list_header_account.key, anchor_account)?;
+                (
+                    anchor_node.prev,
+                    CdllError::PrevAccountMissing,
+                    anchor,
+                    CdllError::AnchorAccountMissing,
+                )
+            }
+        })
     };
-    msg!("6.5");
-    let tail_account_key = head_node.prev;
-    let tail_account = accounts
-        .iter()
-        .find(|a| *a.key == tail_account_key)
-        .ok_or(ProgramError::InvalidAccountData)?;
-    msg!("7");
 
-    let new_node = Node {
-        data,
-        prev: tail_account_key,
-        next: *head_account.key,
+    let new_node = match neighbors {
+        // A single node links to itself
+        None => Node {
+            discriminator: Node::DISCRIMINATOR,
+            version: NODE_VERSION,
+            prev: new_node_key,
+            next: new_node_key,
+            seq,
+            bump,
+            data,
+        },
+        Some((prev_key, _, next_key, _)) => Node {
+            discriminator: Node::DISCRIMINATOR,
+            version: NODE_VERSION,
+            prev: prev_key,
+            next: next_key,
+            seq,
+            bump,
+            data,
+        },
     };
 
-    new_node.serialize(&mut &mut new_node_account.data.borrow_mut()[..])?;
+    // Create the new node account at its PDA
+    create_pda_account(
+        program_id,
+        payer_account,
+        new_node_account,
+        system_program,
+        Node::space(new_node.data.len()),
+        &[
+            NODE_SEED,
+            list_header_account.key.as_ref(),
+            &seq.to_le_bytes(),
+            &[bump],
+        ],
+    )?;
 
-    {
-        msg!("8");
-        let mut tail_data = tail_account.data.borrow_mut();
-        msg!("9");
-        let mut tail_node = Node::try_from_slice(&tail_data)?;
-        msg!("10");
-        tail_node.next = *new_node_account.key;
-        msg!("11");
-        tail_node.serialize(&mut &mut tail_data[..])?;
-    } // Workaround for mutable borrow error
-    msg!("12");
+    if let Some((prev_key, prev_missing, next_key, next_missing)) = neighbors {
+        // prev and next may be the same account, so borrow them one at a time
+        let prev_account = find_account(accounts, &prev_key, prev_missing)?;
+        {
+            let mut prev_node =
+                load_node_prefix_mut(program_id, list_header_account.key, prev_account)?;
+            if prev_node.next != next_key {
+                return Err(CdllError::BrokenLink.into());
+            }
+            prev_node.next = new_node_key;
+        }
 
-    {
-        let mut head_data = head_account.data.borrow_mut();
-        msg!("13");
-        let mut head_node = Node::try_from_slice(&head_data)?;
-        msg!("14");
-        head_node.prev = *new_node_account.key;
-        msg!("15");
-        head_node.serialize(&mut &mut head_data[..])?;
-        msg!("16");
-    } // same here
+        let next_account = find_account(accounts, &next_key, next_missing)?;
+        let mut next_node =
+            load_node_prefix_mut(program_id, list_header_account.key, next_account)?;
+        if next_node.prev != prev_key {
+            return Err(CdllError::BrokenLink.into());
+        }
+        next_node.prev = new_node_key;
+    }
 
-    Ok(())
+    store_node(&new_node, new_node_account)?;
+
+    match position {
+        _ if header.is_empty() => {
+            header.head = new_node_key;
+            header.tail = new_node_key;
+        }
+        InsertPosition::Front => header.head = new_node_key,
+        InsertPosition::Back => header.tail = new_node_key,
+        InsertPosition::After(anchor) if anchor == header.tail => header.tail = new_node_key,
+        InsertPosition::Before(anchor) if anchor == header.head => header.head = new_node_key,
+        InsertPosition::After(_) | InsertPosition::Before(_) => {}
+    }
+    header.len = header.len.checked_add(1).ok_or(CdllError::ListFull)?;
+    header.next_seq = header.next_seq.checked_add(1).ok_or(CdllError::ListFull)?;
+    store_list_header(&header, list_header_account)?;
+
+    Event::NodeAdded {
+        list_header: *list_header_account.key,
+        node: new_node_key,
+        seq,
+        prev: new_node.prev,
+        next: new_node.next,
+        data: new_node.data,
+    }
+    .emit()
+}
+
+/// Which node `remove_node` unlinks and closes.
+enum RemoveTarget {
+    Key(Pubkey),
+    Front,
+    Back,
 }
 
+/// Unlinks and closes the target node, returning its final contents.
 fn remove_node(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
-    target_node_key: Pubkey,
-) -> ProgramResult {
-    msg!("Removing Node: {}", target_node_key);
-
+    target: RemoveTarget,
+) -> Result<Node, ProgramError> {
     let account_info_iter = &mut accounts.iter();
-    let payer_account = next_account_info(account_info_iter)?; // Account paying for the transaction
-    let head_account = next_account_info(account_info_iter)?; // Head node account
+    let authority_account = next_account_info(account_info_iter)?; // List authority
+    let list_header_account = next_account_info(account_info_iter)?; // List header account
     let target_node_account = next_account_info(account_info_iter)?; // Target node account
+    let rent_receiver = next_account_info(account_info_iter)?; // Receives the node's rent
+
+    let mut header = load_list_header(program_id, list_header_account)?;
+    check_authority(&header, authority_account)?;
+    if header.is_empty() {
+        return Err(CdllError::ListEmpty.into());
+    }
 
-    if head_account.owner != program_id || target_node_account.owner != program_id {
-        return Err(ProgramError::IncorrectProgramId);
+    let target_node_key = match target {
+        RemoveTarget::Key(key) => key,
+        RemoveTarget::Front => header.head,
+        RemoveTarget::Back => header.tail,
+    };
+    if target_node_key != *target_node_account.key {
+        return Err(CdllError::InvalidTargetNode.into());
     }
 
     // Deserialize
-    let target_data = target_node_account.data.borrow();
-    let target_node = Node::try_from_slice(&target_data)?;
+    let target_node = load_node(program_id, list_header_account.key, target_node_account)?;
 
-    // previous node
-    {
-        let next_account_key = target_node.next;
-        let prev_account_key = target_node.prev;
-        let prev_account = accounts
-            .iter()
-            .find(|a| *a.key == prev_account_key)
-            .ok_or(ProgramError::InvalidAccountData)?;
-        let mut prev_data = prev_account.data.borrow_mut();
-        let mut prev_node = Node::try_from_slice(&prev_data)?;
-        prev_node.next = next_account_key;
-        prev_node.serialize(&mut &mut prev_data[..])?;
-    }
-
-    // next node
-    {
-        let next_account_key = target_node.next;
-        let prev_account_key = target_node.prev;
-        let next_account = accounts
-            .iter()
-            .find(|a| *a.key == next_account_key)
-            .ok_or(ProgramError::InvalidAccountData)?;
-        let mut next_data = next_account.data.borrow_mut();
-        let mut next_node = Node::try_from_slice(&next_data)?;
-        next_node.prev = prev_account_key;
-        next_node.serialize(&mut &mut next_data[..])?;
-        // If the target node is the head, update the head
-        if *head_account.key == target_node_key {
-            let mut head_data = head_account.data.borrow_mut();
-            head_data.copy_from_slice(&next_data);
-        }
-    }
-
-    // Update the previous and next nodes
-    // prev_node.next = next_account_key;
-    // let mut prev_data_cursor = Cursor::new(&mut **prev_data);
-    // prev_node.serialize(&mut prev_data_cursor)?;
-
-    // next_node.prev = prev_account_key;
-    // let mut next_data_cursor = Cursor::new(&mut **next_data);
-    // next_node.serialize(&mut next_data_cursor)?;
+    if header.len == 1 {
+        // The only node of the list has to be the head, linked to itself
+        if target_node_key != header.head {
+            return Err(CdllError::InvalidTargetNode.into());
+        }
+        if target_node.prev != target_node_key || target_node.next != target_node_key {
+            return Err(CdllError::BrokenLink.into());
+        }
+        // Leave the list empty rather than pointing at a closed account
+        header.head = Pubkey::default();
+        header.tail = Pubkey::default();
+    } else {
+        // Only a single node may link to itself
+        if target_node.prev == target_node_key || target_node.next == target_node_key {
+            return Err(CdllError::BrokenLink.into());
+        }
+
+        // previous node
+        {
+            let prev_account =
+                find_account(accounts, &target_node.prev, CdllError::PrevAccountMissing)?;
+            let mut prev_node =
+                load_node_prefix_mut(program_id, list_header_account.key, prev_account)?;
+            if prev_node.next != target_node_key {
+                return Err(CdllError::BrokenLink.into());
+            }
+            prev_node.next = target_node.next;
+        }
+
+        // next node
+        {
+            let next_account =
+                find_account(accounts, &target_node.next, CdllError::NextAccountMissing)?;
+            let mut next_node =
+                load_node_prefix_mut(program_id, list_header_account.key, next_account)?;
+            if next_node.prev != target_node_key {
+                return Err(CdllError::BrokenLink.into());
+            }
+            next_node.prev = target_node.prev;
+            #[cfg(feature = "bench-relink")]
+            {
+                drop(next_node);
+                bench_relink(
+                    program_id,
+                    list_header_account.key,
+                    next_account,
+                    &target_node.prev,
+                )?;
+            }
+        }
+
+        if header.head == *target_node_account.key {
+            header.head = target_node.next;
+        }
+        if header.tail == *target_node_account.key {
+            header.tail = target_node.prev;
+        }
+    }
+    header.len -= 1;
+    store_list_header(&header, list_header_account)?;
 
     // Deallocate the target node account
-    **payer_account.lamports.borrow_mut() += **target_node_account.lamports.borrow();
-    **target_node_account.lamports.borrow_mut() = 0;
-    // target_node_account.data.borrow_mut().fill(0);
+    close_node_account(target_node_account, rent_receiver)?;
 
+    Event::NodeRemoved {
+        list_header: *list_header_account.key,
+        node: target_node_key,
+        seq: target_node.seq,
+    }
+    .emit()?;
+    Ok(target_node)
+}
+
+/// Removes the head or tail node and hands its data back via return data.
+fn pop_node(program_id: &Pubkey, accounts: &[AccountInfo], target: RemoveTarget) -> ProgramResult {
+    let node = remove_node(program_id, accounts, target)?;
+    set_return_data(&node.data);
     Ok(())
 }
 
+fn set_authority(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    new_authority: Option<Pubkey>,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let authority_account = next_account_info(account_info_iter)?; // Current list authority
+    let list_header_account = next_account_info(account_info_iter)?; // List header account
+
+    let mut header = load_list_header(program_id, list_header_account)?;
+    check_authority(&header, authority_account)?;
+
+    header.authority = new_authority.unwrap_or_default();
+    store_list_header(&header, list_header_account)?;
+
+    Event::AuthorityChanged {
+        list_header: *list_header_account.key,
+        new_authority,
+    }
+    .emit()
+}
+
+/// Replaces the node's payload, provided it currently equals `expected`
+/// when one is given.
+fn update_node(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    node_key: Pubkey,
+    expected: Option<Vec<u8>>,
+    data: Vec<u8>,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let authority_account = next_account_info(account_info_iter)?; // List authority
+    let payer_account = next_account_info(account_info_iter)?; // Pays for or receives the rent difference
+    let list_header_account = next_account_info(account_info_iter)?; // List header account
+    let node_account = next_account_info(account_info_iter)?; // Node to update
+    let system_program = next_account_info(account_info_iter)?;
+
+    if !payer_account.is_signer {
+        return Err(CdllError::MissingSigner.into());
+    }
+
+    let header = load_list_header(program_id, list_header_account)?;
+    check_authority(&header, authority_account)?;
+    check_data_len(&header, &data)?;
+
+    if *node_account.key != node_key {
+        return Err(CdllError::InvalidTargetNode.into());
+    }
+    let mut node = load_node(program_id, list_header_account.key, node_account)?;
+    if expected.is_some_and(|expected| expected != node.data) {
+        return Err(CdllError::DataMismatch.into());
+    }
+    node.data = data;
+
+    let node_size = Node::space(node.data.len());
+    resize_account(node_account, payer_account, system_program, node_size)?;
+    store_node(&node, node_account)?;
+
+    Event::NodeUpdated {
+        list_header: *list_header_account.key,
+        node: node_key,
+        seq: node.seq,
+        data: node.data,
+    }
+    .emit()
+}
+
+fn verify_list(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let list_header_account = next_account_info(account_info_iter)?; // List header account
+    let node_accounts = account_info_iter.as_slice(); // Every node, in order from the head
+
+    let header = load_list_header(program_id, list_header_account)?;
+
+    if node_accounts.len() as u64 != header.len {
+        msg!("Expected {} nodes, got {}", header.len, node_accounts.len());
+        return Err(CdllError::LengthMismatch.into());
+    }
+    let (Some(first_account), Some(last_account)) = (node_accounts.first(), node_accounts.last())
+    else {
+        // An empty list must not point at any node
+        if header.head != Pubkey::default() {
+            msg!("Empty list has a head ({})", header.head);
+            return Err(CdllError::HeadMismatch.into());
+        }
+        if header.tail != Pubkey::default() {
+            msg!("Empty list has a tail ({})", header.tail);
+            return Err(CdllError::TailMismatch.into());
+        }
+        return Ok(());
+    };
+    if *first_account.key != header.head {
+        msg!("Node 0 ({}) is not the head", first_account.key);
+        return Err(CdllError::HeadMismatch.into());
+    }
+    if *last_account.key != header.tail {
+        msg!(
+            "Node {} ({}) is not the tail",
+            node_accounts.len() - 1,
+            last_account.key
+        );
+        return Err(CdllError::TailMismatch.into());
+    }
+
+    // Only the links of the previous node are kept around, so payloads do not
+    // pile up on the heap
+    let mut head_prev = Pubkey::default();
+    let mut prev: Option<(&Pubkey, Pubkey)> = None;
+    for (i, node_account) in node_accounts.iter().enumerate() {
+        let node = load_node_prefix(program_id, list_header_account.key, node_account)
+            .inspect_err(|_| {
+                msg!(
+                    "Node {} ({}) is not a node of this list",
+                    i,
+                    node_account.key
+                )
+            })?;
+        match prev {
+            None => head_prev = node.prev,
+            Some((prev_key, prev_next)) => {
+                if prev_next != *node_account.key {
+                    msg!("Node {}.next does not point at node {}", i - 1, i);
+                    return Err(CdllError::BrokenLink.into());
+                }
+                if node.prev != *prev_key {
+                    msg!("Node {}.prev does not point at node {}", i, i - 1);
+                    return Err(CdllError::BrokenLink.into());
+                }
+            }
+        }
+        prev = Some((node_account.key, node.next));
+    }
+
+    // The tail has to close the circle back to the head
+    let last = node_accounts.len() - 1;
+    if prev.map(|(_, next)| next) != Some(header.head) {
+        msg!("Node {}.next does not point at the head", last);
+        return Err(CdllError::BrokenLink.into());
+    }
+    if head_prev != header.tail {
+        msg!("Node 0.prev does not point at node {}", last);
+        return Err(CdllError::BrokenLink.into());
+    }
+
+    msg!("List of {} nodes verified", header.len);
+    Ok(())
+}
+
+fn clear_list(program_id: &Pubkey, accounts: &[AccountInfo], max_nodes: u32) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let authority_account = next_account_info(account_info_iter)?; // List authority
+    let list_header_account = next_account_info(account_info_iter)?; // List header account
+    let rent_receiver = next_account_info(account_info_iter)?; // Receives the nodes' rent
+    let node_accounts = account_info_iter.as_slice(); // Nodes to close, then new head and tail
+
+    let mut header = load_list_header(program_id, list_header_account)?;
+    check_authority(&header, authority_account)?;
+    if max_nodes == 0 {
+        return Ok(());
+    }
+
+    // Close the supplied nodes for as long as each one is the current head
+    let mut cleared = 0;
+    let mut expected_prev = header.tail;
+    for node_account in node_accounts.iter().take(max_nodes as usize) {
+        if header.is_empty() || *node_account.key != header.head {
+            break;
+        }
+        let node = load_node_prefix(program_id, list_header_account.key, node_account)?;
+        if node.prev != expected_prev {
+            return Err(CdllError::BrokenLink.into());
+        }
+        expected_prev = *node_account.key;
+
+        header.len -= 1;
+        if header.is_empty() {
+            header.head = Pubkey::default();
+            header.tail = Pubkey::default();
+        } else {
+            header.head = node.next;
+        }
+        close_node_account(node_account, rent_receiver)?;
+        Event::NodeRemoved {
+            list_header: *list_header_account.key,
+            node: *node_account.key,
+            seq: node.seq(),
+        }
+        .emit()?;
+        cleared += 1;
+    }
+
+    if header.is_empty() {
+        msg!("Cleared {} nodes, list is empty", cleared);
+        return store_list_header(&header, list_header_account);
+    }
+    if cleared == 0 {
+        return Err(CdllError::HeadAccountMissing.into());
+    }
+
+    // Close the circle over the remaining nodes
+    {
+        let head_account = find_account(accounts, &header.head, CdllError::HeadAccountMissing)?;
+        let mut head_node =
+            load_node_prefix_mut(program_id, list_header_account.key, head_account)?;
+        head_node.prev = header.tail;
+        drop(head_node);
+
+        let tail_account = find_account(accounts, &header.tail, CdllError::TailAccountMissing)?;
+        let mut tail_node =
+            load_node_prefix_mut(program_id, list_header_account.key, tail_account)?;
+        tail_node.next = header.head;
+    }
+
+    msg!("Cleared {} nodes, {} left", cleared, header.len);
+    store_list_header(&header, list_header_account)
+}
+
 // AbTEJTiFgZCMZyHWBMugkPJ4ZayGAxtoap4ChRfUKwv3
 
 // tests
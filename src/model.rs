@@ -0,0 +1,283 @@
+// In-memory reference implementation of a list
+//
+// Mirrors what each instruction does to a list, without accounts, rent or
+// signatures, so the program can be checked against it. Nodes are identified
+// by their sequence number, from which the program derives the node address.
+
+use std::collections::HashMap;
+
+use solana_program::pubkey::Pubkey;
+
+use crate::error::CdllError;
+
+struct ModelNode<T> {
+    prev: u64,
+    next: u64,
+    data: T,
+}
+
+/// A circular doubly linked list with the same rules as a list on chain:
+/// mutations need the authority, payloads are bounded by `max_data_len` and
+/// sequence numbers are never reused.
+pub struct CircularDoublyLinkedList<T> {
+    authority: Option<Pubkey>,
+    max_data_len: usize,
+    nodes: HashMap<u64, ModelNode<T>>,
+    head: Option<u64>,
+    next_seq: u64,
+}
+
+impl<T> CircularDoublyLinkedList<T> {
+    /// Same as `InitializeList`.
+    pub fn new(authority: Pubkey, max_data_len: u32) -> Self {
+        Self {
+            authority: Some(authority),
+            max_data_len: max_data_len as usize,
+            nodes: HashMap::new(),
+            head: None,
+            next_seq: 0,
+        }
+    }
+
+    /// `None` once the authority is renounced.
+    pub fn authority(&self) -> Option<&Pubkey> {
+        self.authority.as_ref()
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn head(&self) -> Option<u64> {
+        self.head
+    }
+
+    pub fn tail(&self) -> Option<u64> {
+        self.head.map(|head| self.nodes[&head].prev)
+    }
+
+    /// Sequence number of the next node to be inserted.
+    pub fn next_seq(&self) -> u64 {
+        self.next_seq
+    }
+
+    pub fn get(&self, seq: u64) -> Option<&T> {
+        self.nodes.get(&seq).map(|node| &node.data)
+    }
+
+    /// The `(prev, next)` sequence numbers of node `seq`.
+    pub fn links(&self, seq: u64) -> Option<(u64, u64)> {
+        self.nodes.get(&seq).map(|node| (node.prev, node.next))
+    }
+
+    /// The nodes in order from the head.
+    pub fn iter(&self) -> impl Iterator<Item = (u64, &T)> + '_ {
+        let mut current = self.head;
+        (0..self.len()).map(move |_| {
+            let seq = current.expect("non-empty list has a head");
+            let node = &self.nodes[&seq];
+            current = Some(node.next);
+            (seq, &node.data)
+        })
+    }
+
+    /// Same as `VerifyList`; fails only if the model itself is corrupted.
+    pub fn verify(&self) -> Result<(), CdllError> {
+        let mut prev = self.tail();
+        for (seq, _) in self.iter() {
+            let node = &self.nodes[&seq];
+            if Some(node.prev) != prev {
+                return Err(CdllError::BrokenLink);
+            }
+            prev = Some(seq);
+        }
+        if prev.map(|tail| self.nodes[&tail].next) != self.head {
+            return Err(CdllError::BrokenLink);
+        }
+        Ok(())
+    }
+
+    fn check_authority(&self, signer: &Pubkey) -> Result<(), CdllError> {
+        match self.authority {
+            None => Err(CdllError::ListImmutable),
+            Some(authority) if authority != *signer => Err(CdllError::InvalidAuthority),
+            Some(_) => Ok(()),
+        }
+    }
+
+    /// Same as `SetAuthority`.
+    pub fn set_authority(
+        &mut self,
+        signer: &Pubkey,
+        new_authority: Option<Pubkey>,
+    ) -> Result<(), CdllError> {
+        self.check_authority(signer)?;
+        self.authority = new_authority;
+        Ok(())
+    }
+
+    /// Links a new node between `prev` and `next`, or as the only node when
+    /// there are none, and returns its sequence number.
+    fn link(&mut self, neighbors: Option<(u64, u64)>, data: T) -> u64 {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        let (prev, next) = neighbors.unwrap_or((seq, seq));
+        self.nodes.insert(seq, ModelNode { prev, next, data });
+        if let Some(prev) = self.nodes.get_mut(&prev) {
+            prev.next = seq;
+        }
+        if let Some(next) = self.nodes.get_mut(&next) {
+            next.prev = seq;
+        }
+        if self.head.is_none() {
+            self.head = Some(seq);
+        }
+        seq
+    }
+
+    fn unlink(&mut self, seq: u64) -> T {
+        let node = self.nodes.remove(&seq).expect("unlinked node exists");
+        if self.nodes.is_empty() {
+            self.head = None;
+        } else {
+            self.nodes.get_mut(&node.prev).expect("prev exists").next = node.next;
+            self.nodes.get_mut(&node.next).expect("next exists").prev = node.prev;
+            if self.head == Some(seq) {
+                self.head = Some(node.next);
+            }
+        }
+        node.data
+    }
+
+    /// Same as `RemoveNode`; returns the payload of the removed node.
+    pub fn remove(&mut self, signer: &Pubkey, seq: u64) -> Result<T, CdllError> {
+        self.check_authority(signer)?;
+        if self.is_empty() {
+            return Err(CdllError::ListEmpty);
+        }
+        if !self.nodes.contains_key(&seq) {
+            return Err(CdllError::NodeNotInList);
+        }
+        Ok(self.unlink(seq))
+    }
+
+    /// Same as `PopFront`.
+    pub fn pop_front(&mut self, signer: &Pubkey) -> Result<T, CdllError> {
+        self.check_authority(signer)?;
+        let head = self.head.ok_or(CdllError::ListEmpty)?;
+        Ok(self.unlink(head))
+    }
+
+    /// Same as `PopBack`.
+    pub fn pop_back(&mut self, signer: &Pubkey) -> Result<T, CdllError> {
+        self.check_authority(signer)?;
+        let tail = self.tail().ok_or(CdllError::ListEmpty)?;
+        Ok(self.unlink(tail))
+    }
+
+    /// Same as `ClearList`; returns the payloads of the removed nodes in
+    /// order.
+    pub fn clear(&mut self, signer: &Pubkey, max_nodes: u32) -> Result<Vec<T>, CdllError> {
+        self.check_authority(signer)?;
+        let mut cleared = vec![];
+        while let Some(head) = self.head.filter(|_| cleared.len() < max_nodes as usize) {
+            cleared.push(self.unlink(head));
+        }
+        Ok(cleared)
+    }
+}
+
+impl<T: AsRef<[u8]>> CircularDoublyLinkedList<T> {
+    fn check_data_len(&self, data: &T) -> Result<(), CdllError> {
+        if data.as_ref().len() > self.max_data_len {
+            return Err(CdllError::DataTooLarge);
+        }
+        Ok(())
+    }
+
+    /// Same as `AddNode` and `PushBack`; returns the new node's sequence
+    /// number.
+    pub fn push_back(&mut self, signer: &Pubkey, data: T) -> Result<u64, CdllError> {
+        self.check_authority(signer)?;
+        self.check_data_len(&data)?;
+        let neighbors = self.tail().zip(self.head);
+        Ok(self.link(neighbors, data))
+    }
+
+    /// Same as `PushFront`.
+    pub fn push_front(&mut self, signer: &Pubkey, data: T) -> Result<u64, CdllError> {
+        let seq = self.push_back(signer, data)?;
+        self.head = Some(seq);
+        Ok(seq)
+    }
+
+    /// Same as `InsertAfter`.
+    pub fn insert_after(
+        &mut self,
+        signer: &Pubkey,
+        anchor: u64,
+        data: T,
+    ) -> Result<u64, CdllError> {
+        self.check_authority(signer)?;
+        self.check_data_len(&data)?;
+        if self.is_empty() {
+            return Err(CdllError::ListEmpty);
+        }
+        let (_, next) = self.links(anchor).ok_or(CdllError::NodeNotInList)?;
+        Ok(self.link(Some((anchor, next)), data))
+    }
+
+    /// Same as `InsertBefore`. Inserting before the head makes the new node
+    /// the head.
+    pub fn insert_before(
+        &mut self,
+        signer: &Pubkey,
+        anchor: u64,
+        data: T,
+    ) -> Result<u64, CdllError> {
+        self.check_authority(signer)?;
+        self.check_data_len(&data)?;
+        if self.is_empty() {
+            return Err(CdllError::ListEmpty);
+        }
+        let (prev, _) = self.links(anchor).ok_or(CdllError::NodeNotInList)?;
+        let seq = self.link(Some((prev, anchor)), data);
+        // A node inserted before the head becomes the head
+        if self.head == Some(anchor) {
+            self.head = Some(seq);
+        }
+        Ok(seq)
+    }
+
+    /// Same as `UpdateNode`; returns the previous payload.
+    pub fn update(&mut self, signer: &Pubkey, seq: u64, data: T) -> Result<T, CdllError> {
+        self.check_authority(signer)?;
+        self.check_data_len(&data)?;
+        let node = self.nodes.get_mut(&seq).ok_or(CdllError::NodeNotInList)?;
+        Ok(std::mem::replace(&mut node.data, data))
+    }
+
+    /// Same as `UpdateNodeIfEquals`.
+    pub fn update_if_equals(
+        &mut self,
+        signer: &Pubkey,
+        seq: u64,
+        expected: &T,
+        new: T,
+    ) -> Result<T, CdllError>
+    where
+        T: PartialEq,
+    {
+        self.check_authority(signer)?;
+        self.check_data_len(&new)?;
+        let node = self.nodes.get_mut(&seq).ok_or(CdllError::NodeNotInList)?;
+        if node.data != *expected {
+            return Err(CdllError::DataMismatch);
+        }
+        Ok(std::mem::replace(&mut node.data, new))
+    }
+}
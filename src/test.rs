@@ -2,11 +2,19 @@
 
 #[cfg(test)]
 mod tests {
-    use crate::{process_instruction, InstructionData, Node};
+    use crate::{
+        error::CdllError, event::Event, find_node_address, instruction, process_instruction,
+        InstructionData, ListHeader, Node, NodePrefix, LIST_HEADER_VERSION, MAX_NODE_DATA_LEN,
+        NODE_VERSION,
+    };
 
+    use crate::event::{decode_events, PROGRAM_DATA_LOG_PREFIX};
+    use base64::{engine::general_purpose::STANDARD, Engine};
     use borsh::{BorshDeserialize, BorshSerialize};
     use solana_program::{
-        instruction::{AccountMeta, Instruction},
+        decode_error::DecodeError,
+        instruction::{AccountMeta, Instruction, InstructionError},
+        program_error::ProgramError,
         program_pack::Pack,
         pubkey::Pubkey,
         rent::Rent,
@@ -15,103 +23,201 @@ mod tests {
     use solana_program_test::*;
     use solana_sdk::{
         account::Account,
+        hash::Hash,
         signature::{Keypair, Signer},
         system_program,
-        transaction::Transaction,
+        transaction::{Transaction, TransactionError},
         transport::TransportError,
     };
 
+    const MAX_DATA_LEN: u32 = 32;
+
+    fn assert_cdll_error(result: Result<(), BanksClientError>, expected: CdllError) {
+        assert_eq!(
+            result.unwrap_err().unwrap(),
+            TransactionError::InstructionError(0, InstructionError::Custom(expected as u32))
+        );
+    }
+
+    fn initialize_list_instruction(
+        program_id: Pubkey,
+        payer: &Keypair,
+        list_header: &Keypair,
+    ) -> Instruction {
+        instruction::initialize_list(
+            &program_id,
+            &payer.pubkey(),
+            &list_header.pubkey(),
+            MAX_DATA_LEN,
+        )
+    }
+
+    async fn get_list_header(banks_client: &mut BanksClient, list_header: Pubkey) -> ListHeader {
+        let list_header_data = banks_client
+            .get_account(list_header)
+            .await
+            .unwrap()
+            .unwrap()
+            .data;
+        ListHeader::try_from_slice(&list_header_data).unwrap()
+    }
+
+    async fn get_node(banks_client: &mut BanksClient, node: Pubkey) -> Node {
+        let node_data = banks_client.get_account(node).await.unwrap().unwrap().data;
+        Node::try_from_slice(&node_data).unwrap()
+    }
+
+    /// Walks the list from the head, checking the links in both directions, and
+    /// returns the node keys and data in order.
+    async fn walk_list(
+        banks_client: &mut BanksClient,
+        list_header: Pubkey,
+    ) -> Vec<(Pubkey, Vec<u8>)> {
+        let header = get_list_header(banks_client, list_header).await;
+        let mut nodes = Vec::new();
+        let mut prev = header.tail;
+        let mut current = header.head;
+        for _ in 0..header.len {
+            let node = get_node(banks_client, current).await;
+            assert_eq!(node.prev, prev);
+            nodes.push((current, node.data));
+            prev = current;
+            current = node.next;
+        }
+        assert_eq!(prev, header.tail);
+        assert_eq!(current, header.head);
+        nodes
+    }
+
+    /// Writes a list header and `len` nodes straight into the test ledger,
+    /// linking node `i` to the `(prev, next)` returned by `links(i, nodes)`.
+    /// Used to set up lists the program itself would never produce.
+    fn add_list_accounts(
+        program_test: &mut ProgramTest,
+        program_id: Pubkey,
+        authority: Pubkey,
+        len: usize,
+        links: impl Fn(usize, &[Pubkey]) -> (Pubkey, Pubkey),
+    ) -> (Pubkey, Vec<Pubkey>) {
+        let list_header = Pubkey::new_unique();
+        let nodes: Vec<(Pubkey, u8)> = (0..len as u64)
+            .map(|seq| find_node_address(&program_id, &list_header, seq))
+            .collect();
+        let node_keys: Vec<Pubkey> = nodes.iter().map(|(key, _)| *key).collect();
+
+        let rent = Rent::default();
+        let mut add_account = |key: Pubkey, data: Vec<u8>| {
+            program_test.add_account(
+                key,
+                Account {
+                    lamports: rent.minimum_balance(data.len()),
+                    data,
+                    owner: program_id,
+                    executable: false,
+                    rent_epoch: 0,
+                },
+            );
+        };
+
+        let header = ListHeader {
+            discriminator: ListHeader::DISCRIMINATOR,
+            version: LIST_HEADER_VERSION,
+            authority,
+            head: node_keys[0],
+            tail: node_keys[len - 1],
+            len: len as u64,
+            next_seq: len as u64,
+            max_data_len: MAX_DATA_LEN,
+        };
+        add_account(list_header, borsh::to_vec(&header).unwrap());
+        for (i, (key, bump)) in nodes.iter().enumerate() {
+            let (prev, next) = links(i, &node_keys);
+            let node = Node {
+                discriminator: Node::DISCRIMINATOR,
+                version: NODE_VERSION,
+                prev,
+                next,
+                seq: i as u64,
+                bump: *bump,
+                data: vec![i as u8],
+            };
+            add_account(*key, borsh::to_vec(&node).unwrap());
+        }
+
+        (list_header, node_keys)
+    }
+
     #[tokio::test]
     async fn test_initialize_list() {
         let program_id = Pubkey::new_unique();
-        let mut program_test = ProgramTest::new(
+        let program_test = ProgramTest::new(
             "circular_doubly_ll_solana",
             program_id,
             processor!(process_instruction),
         );
 
-        // Create a new head account
-        let head_account = Keypair::new();
-        let node_size = std::mem::size_of::<Node>();
-        let rent = Rent::default();
-        let required_lamports = rent.minimum_balance(node_size);
-
-        program_test.add_account(
-            head_account.pubkey(),
-            Account {
-                lamports: required_lamports,
-                data: vec![0_u8; node_size],
-                owner: program_id,
-                executable: false,
-                rent_epoch: 0,
-            },
-        );
+        let list_header_account = Keypair::new();
 
-        // let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
-        let ProgramTestContext {
-            mut banks_client,
-            last_blockhash,
-            payer,
-            ..
-        } = program_test.start_with_context().await;
+        let mut context = program_test.start_with_context().await;
+        let payer = context.payer.insecure_clone();
 
-        let instruction_data = InstructionData::InitializeList.try_to_vec().unwrap();
-        let instruction = Instruction {
-            program_id,
-            accounts: vec![
-                AccountMeta::new(payer.pubkey(), true),
-                AccountMeta::new(head_account.pubkey(), false),
-            ],
-            data: instruction_data,
-        };
+        let instruction = initialize_list_instruction(program_id, &payer, &list_header_account);
 
         let transaction = Transaction::new_signed_with_payer(
             &[instruction],
             Some(&payer.pubkey()),
-            &[&payer],
-            last_blockhash,
+            &[&payer, &list_header_account],
+            context.last_blockhash,
         );
 
-        banks_client.process_transaction(transaction).await.unwrap();
+        context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
 
-        let head_account_data = banks_client
-            .get_account(head_account.pubkey())
+        let list_account = context
+            .banks_client
+            .get_account(list_header_account.pubkey())
             .await
             .unwrap()
-            .unwrap()
-            .data;
+            .unwrap();
+        assert_eq!(list_account.owner, program_id);
+        assert_eq!(list_account.data.len(), ListHeader::LEN);
+
+        let list_header = ListHeader::try_from_slice(&list_account.data).unwrap();
 
-        let head_node = Node::try_from_slice(&head_account_data).unwrap();
+        assert_eq!(list_header.discriminator, ListHeader::DISCRIMINATOR);
+        assert_eq!(list_header.version, LIST_HEADER_VERSION);
+        assert_eq!(list_header.authority, payer.pubkey());
+        assert_eq!(list_header.head, Pubkey::default());
+        assert_eq!(list_header.tail, Pubkey::default());
+        assert_eq!(list_header.len, 0);
 
-        assert_eq!(head_node.data, 0);
-        assert_eq!(head_node.prev, head_account.pubkey());
-        assert_eq!(head_node.next, head_account.pubkey());
+        // A second initialization of the same header must fail
+        let instruction = initialize_list_instruction(program_id, &payer, &list_header_account);
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction],
+            Some(&payer.pubkey()),
+            &[&payer, &list_header_account],
+            context.get_new_latest_blockhash().await.unwrap(),
+        );
+        assert_cdll_error(
+            context.banks_client.process_transaction(transaction).await,
+            CdllError::ListAlreadyInitialized,
+        );
     }
 
     #[tokio::test]
     async fn test_add_node() {
         let program_id = Pubkey::new_unique();
-        let mut program_test = ProgramTest::new(
+        let program_test = ProgramTest::new(
             "circular_doubly_ll_solana",
             program_id,
             processor!(process_instruction),
         );
 
-        let head_account = Keypair::new();
-        let node_size = std::mem::size_of::<Node>();
-        let rent = Rent::default();
-        let required_lamports = rent.minimum_balance(node_size);
-
-        program_test.add_account(
-            head_account.pubkey(),
-            Account {
-                lamports: required_lamports,
-                data: vec![0_u8; node_size],
-                owner: program_id,
-                executable: false,
-                rent_epoch: 0,
-            },
-        );
+        let list_header_account = Keypair::new();
 
         // let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
         let ProgramTestContext {
@@ -121,20 +227,13 @@ mod tests {
             ..
         } = program_test.start_with_context().await;
 
-        let instruction_data = InstructionData::InitializeList.try_to_vec().unwrap();
-        let initialize_instruction = Instruction {
-            program_id,
-            accounts: vec![
-                AccountMeta::new(payer.pubkey(), true),
-                AccountMeta::new(head_account.pubkey(), false),
-            ],
-            data: instruction_data,
-        };
+        let initialize_instruction =
+            initialize_list_instruction(program_id, &payer, &list_header_account);
 
         let initialize_transaction = Transaction::new_signed_with_payer(
             &[initialize_instruction],
             Some(&payer.pubkey()),
-            &[&payer],
+            &[&payer, &list_header_account],
             last_blockhash,
         );
 
@@ -143,110 +242,139 @@ mod tests {
             .await
             .unwrap();
 
-        let new_node_account = Keypair::new();
+        let (new_node_account, _) =
+            find_node_address(&program_id, &list_header_account.pubkey(), 0);
 
-        let _create_new_node_account_ix = system_instruction::create_account(
-            &payer.pubkey(),
-            &new_node_account.pubkey(),
-            required_lamports,
-            node_size as u64,
+        let add_node_instruction = instruction::add_node(
             &program_id,
+            &payer.pubkey(),
+            &payer.pubkey(),
+            &list_header_account.pubkey(),
+            &new_node_account,
+            &[],
+            vec![42],
+        );
+
+        let transaction = Transaction::new_signed_with_payer(
+            &[add_node_instruction],
+            Some(&payer.pubkey()),
+            &[&payer],
+            last_blockhash,
         );
-        let head_account_data = banks_client
-            .get_account(head_account.pubkey())
+
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        let list_header = get_list_header(&mut banks_client, list_header_account.pubkey()).await;
+        let new_node = get_node(&mut banks_client, new_node_account).await;
+
+        assert_eq!(list_header.head, new_node_account);
+        assert_eq!(list_header.tail, new_node_account);
+        assert_eq!(list_header.len, 1);
+        assert_eq!(list_header.next_seq, 1);
+        assert_eq!(new_node.data, vec![42]);
+        assert_eq!(new_node.seq, 0);
+        assert_eq!(new_node.prev, new_node_account);
+        assert_eq!(new_node.next, new_node_account);
+
+        // The program created the node account, rent exempt and owned by the program
+        let new_node_account_info = banks_client
+            .get_account(new_node_account)
             .await
             .unwrap()
-            .unwrap()
-            .data;
-        let head_node = Node::try_from_slice(&head_account_data).unwrap();
+            .unwrap();
+        let rent = banks_client.get_rent().await.unwrap();
+        assert_eq!(new_node_account_info.owner, program_id);
+        assert_eq!(new_node_account_info.data.len(), Node::space(1));
+        assert!(rent.is_exempt(
+            new_node_account_info.lamports,
+            new_node_account_info.data.len()
+        ));
+    }
 
-        let add_node_instruction_data = InstructionData::AddNode { data: 42 }.try_to_vec().unwrap();
-        println!("add_node_instruction_data: {:?}", add_node_instruction_data);
-        let add_node_instruction = Instruction {
+    #[tokio::test]
+    async fn test_prefunded_node_address() {
+        let program_id = Pubkey::new_unique();
+        let program_test = ProgramTest::new(
+            "circular_doubly_ll_solana",
             program_id,
-            accounts: vec![
-                AccountMeta::new(head_account.pubkey(), false),
-                AccountMeta::new(head_node.prev, false),
-                AccountMeta::new(new_node_account.pubkey(), false),
-                AccountMeta::new(system_program::id(), false),
-            ],
-            data: add_node_instruction_data,
-        };
+            processor!(process_instruction),
+        );
+        let (mut banks_client, payer, last_blockhash) = program_test.start().await;
 
+        let list_header_account = Keypair::new();
+        let list = list_header_account.pubkey();
         let transaction = Transaction::new_signed_with_payer(
-            &[_create_new_node_account_ix, add_node_instruction],
+            &[initialize_list_instruction(
+                program_id,
+                &payer,
+                &list_header_account,
+            )],
             Some(&payer.pubkey()),
-            &[&payer, &new_node_account],
+            &[&payer, &list_header_account],
             last_blockhash,
         );
-
         banks_client.process_transaction(transaction).await.unwrap();
 
-        let head_account_data = banks_client
-            .get_account(head_account.pubkey())
-            .await
-            .unwrap()
-            .unwrap()
-            .data;
+        // Anyone can fund the next node addresses before they are created: the
+        // first with less than a node's rent, the second with more
+        let rent = banks_client.get_rent().await.unwrap();
+        let nodes = [0, 1].map(|seq| find_node_address(&program_id, &list, seq).0);
+        let prefunded = [rent.minimum_balance(0), rent.minimum_balance(0) * 10];
+        let transaction = Transaction::new_signed_with_payer(
+            &[
+                system_instruction::transfer(&payer.pubkey(), &nodes[0], prefunded[0]),
+                system_instruction::transfer(&payer.pubkey(), &nodes[1], prefunded[1]),
+            ],
+            Some(&payer.pubkey()),
+            &[&payer],
+            last_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
 
-        let new_node_data = banks_client
-            .get_account(new_node_account.pubkey())
-            .await
-            .unwrap()
-            .unwrap()
-            .data;
+        let neighbors = [vec![], vec![nodes[0], nodes[0]]];
+        for (i, node) in nodes.iter().enumerate() {
+            let transaction = Transaction::new_signed_with_payer(
+                &[instruction::add_node(
+                    &program_id,
+                    &payer.pubkey(),
+                    &payer.pubkey(),
+                    &list,
+                    node,
+                    &neighbors[i],
+                    vec![i as u8],
+                )],
+                Some(&payer.pubkey()),
+                &[&payer],
+                last_blockhash,
+            );
+            banks_client.process_transaction(transaction).await.unwrap();
 
-        let head_node = Node::try_from_slice(&head_account_data).unwrap();
-        let new_node = Node::try_from_slice(&new_node_data).unwrap();
-        {
-            println!("new_node.prev: {:?}", new_node.prev);
-            println!("head_node.prev: {:?}", head_node.prev);
-            println!("new_node.next: {:?}", new_node.next);
-            println!("head_node.next: {:?}", head_node.next);
+            let account = banks_client.get_account(*node).await.unwrap().unwrap();
+            assert_eq!(account.owner, program_id);
+            assert_eq!(account.data.len(), Node::space(1));
+            assert_eq!(
+                account.lamports,
+                rent.minimum_balance(Node::space(1)).max(prefunded[i])
+            );
         }
-
-        assert_eq!(head_node.prev, new_node_account.pubkey());
-        assert_eq!(new_node.next, head_account.pubkey());
-        assert_eq!(new_node.data, 42);
-        assert_eq!(new_node_account.pubkey(), head_node.prev);
-        assert_eq!(head_account.pubkey(), new_node.next);
+        assert_eq!(
+            walk_list(&mut banks_client, list).await,
+            vec![(nodes[0], vec![0]), (nodes[1], vec![1])]
+        );
     }
 
     #[tokio::test]
     async fn test_remove_node() {
         let program_id = Pubkey::new_unique();
-        let mut program_test = ProgramTest::new(
+        let program_test = ProgramTest::new(
             "circular_doubly_ll_solana",
             program_id,
             processor!(process_instruction),
         );
 
-        let head_account = Keypair::new();
-        let node1_account = Keypair::new();
-        let node2_account = Keypair::new();
-        let node_size = std::mem::size_of::<Node>();
-        let rent = Rent::default();
-        let required_lamports = rent.minimum_balance(node_size);
-
-        // environment setup
-        let accounts = vec![
-            (head_account.pubkey(), head_account.insecure_clone()),
-            (node1_account.pubkey(), node1_account.insecure_clone()),
-            (node2_account.pubkey(), node2_account.insecure_clone()),
-        ];
-
-        for (pubkey, _account) in &accounts {
-            program_test.add_account(
-                *pubkey,
-                Account {
-                    lamports: required_lamports,
-                    data: vec![0_u8; node_size],
-                    owner: program_id,
-                    executable: false,
-                    rent_epoch: 0,
-                },
-            );
-        }
+        let list_header_account = Keypair::new();
+        let (node1_account, _) = find_node_address(&program_id, &list_header_account.pubkey(), 0);
+        let (node2_account, _) = find_node_address(&program_id, &list_header_account.pubkey(), 1);
 
         // let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
         let ProgramTestContext {
@@ -256,19 +384,13 @@ mod tests {
             ..
         } = program_test.start_with_context().await;
 
-        let initialize_instruction = Instruction {
-            program_id,
-            accounts: vec![
-                AccountMeta::new(payer.pubkey(), true),
-                AccountMeta::new(head_account.pubkey(), false),
-            ],
-            data: InstructionData::InitializeList.try_to_vec().unwrap(),
-        };
+        let initialize_instruction =
+            initialize_list_instruction(program_id, &payer, &list_header_account);
 
         let initialize_transaction = Transaction::new_signed_with_payer(
             &[initialize_instruction],
             Some(&payer.pubkey()),
-            &[&payer],
+            &[&payer, &list_header_account],
             last_blockhash,
         );
 
@@ -277,42 +399,21 @@ mod tests {
             .await
             .unwrap();
 
-        let head_account_data = banks_client
-            .get_account(head_account.pubkey())
-            .await
-            .unwrap()
-            .unwrap()
-            .data;
-        let head_node = Node::try_from_slice(&head_account_data).unwrap();
-
         // Add node1
-        let add_node1_instruction = Instruction {
-            program_id,
-            accounts: vec![
-                AccountMeta::new(head_account.pubkey(), false),
-                AccountMeta::new(head_node.prev, false),
-                AccountMeta::new(node1_account.pubkey(), false),
-                AccountMeta::new(system_program::id(), false),
-            ],
-            data: InstructionData::AddNode { data: 100 }.try_to_vec().unwrap(),
-        };
+        let add_node1_instruction = instruction::add_node(
+            &program_id,
+            &payer.pubkey(),
+            &payer.pubkey(),
+            &list_header_account.pubkey(),
+            &node1_account,
+            &[],
+            vec![100],
+        );
 
         let transaction1 = Transaction::new_signed_with_payer(
-            &[
-                // system_instruction::create_account(
-                //     &payer.pubkey(),
-                //     &node1_account.pubkey(),
-                //     required_lamports,
-                //     node_size as u64,
-                //     &program_id,
-                // ),
-                add_node1_instruction,
-            ],
+            &[add_node1_instruction],
             Some(&payer.pubkey()),
-            &[
-                &payer,
-                // &node1_account
-            ],
+            &[&payer],
             last_blockhash,
         );
 
@@ -321,42 +422,21 @@ mod tests {
             .await
             .unwrap();
 
-        let head_account_data = banks_client
-            .get_account(head_account.pubkey())
-            .await
-            .unwrap()
-            .unwrap()
-            .data;
-        let head_node = Node::try_from_slice(&head_account_data).unwrap();
-
         // Add node2
-        let add_node2_instruction = Instruction {
-            program_id,
-            accounts: vec![
-                AccountMeta::new(head_account.pubkey(), false),
-                AccountMeta::new(head_node.prev, false),
-                AccountMeta::new(node2_account.pubkey(), false),
-                AccountMeta::new(system_program::id(), false),
-            ],
-            data: InstructionData::AddNode { data: 200 }.try_to_vec().unwrap(),
-        };
+        let add_node2_instruction = instruction::add_node(
+            &program_id,
+            &payer.pubkey(),
+            &payer.pubkey(),
+            &list_header_account.pubkey(),
+            &node2_account,
+            &[node1_account],
+            vec![200],
+        );
 
         let transaction2 = Transaction::new_signed_with_payer(
-            &[
-                // system_instruction::create_account(
-                //     &payer.pubkey(),
-                //     &node2_account.pubkey(),
-                //     required_lamports,
-                //     node_size as u64,
-                //     &program_id,
-                // ),
-                add_node2_instruction,
-            ],
+            &[add_node2_instruction],
             Some(&payer.pubkey()),
-            &[
-                &payer,
-                // &node2_account
-            ],
+            &[&payer],
             last_blockhash,
         );
 
@@ -365,30 +445,47 @@ mod tests {
             .await
             .unwrap();
 
-        let target_node_data = banks_client
-            .get_account(node1_account.pubkey())
-            .await
-            .unwrap()
-            .unwrap()
-            .data;
-        let target_node = Node::try_from_slice(&target_node_data).unwrap();
+        let list_header = get_list_header(&mut banks_client, list_header_account.pubkey()).await;
+        assert_eq!(list_header.head, node1_account);
+        assert_eq!(list_header.tail, node2_account);
+        assert_eq!(list_header.len, 2);
+
+        let target_node = get_node(&mut banks_client, node1_account).await;
+        let node1_lamports = banks_client.get_balance(node1_account).await.unwrap();
+        let rent_receiver = Keypair::new();
 
         // Remove node1
-        let remove_node1_instruction = Instruction {
-            program_id,
-            accounts: vec![
-                AccountMeta::new(payer.pubkey(), true),
-                AccountMeta::new(head_account.pubkey(), false),
-                AccountMeta::new(node1_account.pubkey(), false),
-                AccountMeta::new(target_node.prev, false),
-                AccountMeta::new(target_node.next, false),
+        let remove_node1_instruction = instruction::remove_node(
+            &program_id,
+            &payer.pubkey(),
+            &list_header_account.pubkey(),
+            &node1_account,
+            &rent_receiver.pubkey(),
+            &[target_node.prev, target_node.next],
+        );
+
+        // A closed node cannot be touched again within the same transaction
+        let double_remove_transaction = Transaction::new_signed_with_payer(
+            &[
+                remove_node1_instruction.clone(),
+                remove_node1_instruction.clone(),
             ],
-            data: InstructionData::RemoveNode {
-                target_node: node1_account.pubkey(),
-            }
-            .try_to_vec()
-            .unwrap(),
-        };
+            Some(&payer.pubkey()),
+            &[&payer],
+            last_blockhash,
+        );
+
+        assert_eq!(
+            banks_client
+                .process_transaction(double_remove_transaction)
+                .await
+                .unwrap_err()
+                .unwrap(),
+            TransactionError::InstructionError(
+                1,
+                InstructionError::Custom(CdllError::NodeClosed as u32)
+            )
+        );
 
         let transaction3 = Transaction::new_signed_with_payer(
             &[remove_node1_instruction],
@@ -402,63 +499,103 @@ mod tests {
             .await
             .unwrap();
 
+        // The rent went to the designated receiver
+        assert_eq!(
+            banks_client
+                .get_balance(rent_receiver.pubkey())
+                .await
+                .unwrap(),
+            node1_lamports
+        );
+
         // Verify node1 is removed
-        let node1_account_data = banks_client
-            .get_account(node1_account.pubkey())
-            .await
-            .unwrap();
+        let node1_account_data = banks_client.get_account(node1_account).await.unwrap();
 
         assert!(node1_account_data.is_none()); // Account should be decommisioned
 
-        // Verify head and node2 pointers
-        let head_account_data = banks_client
-            .get_account(head_account.pubkey())
+        // Verify the header and node2 pointers
+        let list_header = get_list_header(&mut banks_client, list_header_account.pubkey()).await;
+        let node2_node = get_node(&mut banks_client, node2_account).await;
+
+        assert_eq!(list_header.head, node2_account);
+        assert_eq!(list_header.tail, node2_account);
+        assert_eq!(list_header.len, 1);
+        assert_eq!(node2_node.prev, node2_account);
+        assert_eq!(node2_node.next, node2_account);
+        // node2 kept its own contents instead of being overwritten
+        assert_eq!(node2_node.seq, 1);
+        assert_eq!(node2_node.data, vec![200]);
+
+        // Removing the last node leaves an empty list
+        let remove_node2_instruction = instruction::remove_node(
+            &program_id,
+            &payer.pubkey(),
+            &list_header_account.pubkey(),
+            &node2_account,
+            &payer.pubkey(),
+            &[],
+        );
+        let transaction4 = Transaction::new_signed_with_payer(
+            &[remove_node2_instruction],
+            Some(&payer.pubkey()),
+            &[&payer],
+            last_blockhash,
+        );
+        banks_client
+            .process_transaction(transaction4)
             .await
-            .unwrap()
-            .unwrap()
-            .data;
+            .unwrap();
 
-        let node2_account_data = banks_client
-            .get_account(node2_account.pubkey())
+        assert!(banks_client
+            .get_account(node2_account)
             .await
             .unwrap()
-            .unwrap()
-            .data;
-
-        let head_node = Node::try_from_slice(&head_account_data).unwrap();
-        let node2_node = Node::try_from_slice(&node2_account_data).unwrap();
+            .is_none());
+        let list_header = get_list_header(&mut banks_client, list_header_account.pubkey()).await;
+        assert!(list_header.is_empty());
+        assert_eq!(list_header.head, Pubkey::default());
+        assert_eq!(list_header.tail, Pubkey::default());
+        assert_eq!(list_header.len, 0);
+
+        // The emptied list accepts new nodes again
+        let (node3_account, _) = find_node_address(&program_id, &list_header_account.pubkey(), 2);
+        let add_node3_instruction = instruction::add_node(
+            &program_id,
+            &payer.pubkey(),
+            &payer.pubkey(),
+            &list_header_account.pubkey(),
+            &node3_account,
+            &[],
+            vec![44],
+        );
+        let transaction5 = Transaction::new_signed_with_payer(
+            &[add_node3_instruction],
+            Some(&payer.pubkey()),
+            &[&payer],
+            last_blockhash,
+        );
+        banks_client
+            .process_transaction(transaction5)
+            .await
+            .unwrap();
 
-        assert_eq!(head_node.prev, node2_account.pubkey());
-        assert_eq!(head_node.next, node2_account.pubkey());
-        assert_eq!(node2_node.prev, head_account.pubkey());
-        assert_eq!(node2_node.next, head_account.pubkey());
+        assert_eq!(
+            walk_list(&mut banks_client, list_header_account.pubkey()).await,
+            vec![(node3_account, vec![44])]
+        );
     }
 
     #[tokio::test]
     async fn test_full_list_operations() {
         // Integration test
         let program_id = Pubkey::new_unique();
-        let mut program_test = ProgramTest::new(
+        let program_test = ProgramTest::new(
             "circular_doubly_ll_solana",
             program_id,
             processor!(process_instruction),
         );
 
-        let head_account = Keypair::new();
-        let node_size = std::mem::size_of::<Node>();
-        let rent = Rent::default();
-        let required_lamports = rent.minimum_balance(node_size);
-
-        program_test.add_account(
-            head_account.pubkey(),
-            Account {
-                lamports: required_lamports,
-                data: vec![0_u8; node_size],
-                owner: program_id,
-                executable: false,
-                rent_epoch: 0,
-            },
-        );
+        let list_header_account = Keypair::new();
 
         // let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
         let ProgramTestContext {
@@ -468,19 +605,13 @@ mod tests {
             ..
         } = program_test.start_with_context().await;
 
-        let initialize_instruction = Instruction {
-            program_id,
-            accounts: vec![
-                AccountMeta::new(payer.pubkey(), true),
-                AccountMeta::new(head_account.pubkey(), false),
-            ],
-            data: InstructionData::InitializeList.try_to_vec().unwrap(),
-        };
+        let initialize_instruction =
+            initialize_list_instruction(program_id, &payer, &list_header_account);
 
         let initialize_transaction = Transaction::new_signed_with_payer(
             &[initialize_instruction],
             Some(&payer.pubkey()),
-            &[&payer],
+            &[&payer, &list_header_account],
             last_blockhash,
         );
 
@@ -491,72 +622,62 @@ mod tests {
 
         let mut node_accounts = Vec::new();
         for i in 1..=5 {
-            let node_account = Keypair::new();
+            let (node_account, _) =
+                find_node_address(&program_id, &list_header_account.pubkey(), i as u64 - 1);
             node_accounts.push(node_account);
 
-            let head_account_data = banks_client
-                .get_account(head_account.pubkey())
-                .await
-                .unwrap()
-                .unwrap()
-                .data;
-            let head_node = Node::try_from_slice(&head_account_data).unwrap();
+            let list_header =
+                get_list_header(&mut banks_client, list_header_account.pubkey()).await;
 
-            let add_node_instruction = Instruction {
-                program_id,
-                accounts: vec![
-                    AccountMeta::new(head_account.pubkey(), false),
-                    AccountMeta::new(head_node.prev, false),
-                    AccountMeta::new(node_accounts[i - 1].pubkey(), false),
-                    AccountMeta::new(system_program::id(), false),
-                ],
-                data: InstructionData::AddNode { data: i as u64 }
-                    .try_to_vec()
-                    .unwrap(),
+            let neighbors = if list_header.is_empty() {
+                vec![]
+            } else {
+                vec![list_header.head, list_header.tail]
             };
+            let add_node_instruction = instruction::add_node(
+                &program_id,
+                &payer.pubkey(),
+                &payer.pubkey(),
+                &list_header_account.pubkey(),
+                &node_accounts[i - 1],
+                &neighbors,
+                vec![i as u8],
+            );
 
             let transaction = Transaction::new_signed_with_payer(
-                &[
-                    system_instruction::create_account(
-                        &payer.pubkey(),
-                        &node_accounts[i - 1].pubkey(),
-                        required_lamports,
-                        node_size as u64,
-                        &program_id,
-                    ),
-                    add_node_instruction,
-                ],
+                &[add_node_instruction],
                 Some(&payer.pubkey()),
-                &[&payer, &node_accounts[i - 1]],
+                &[&payer],
                 last_blockhash,
             );
 
             banks_client.process_transaction(transaction).await.unwrap();
         }
 
+        // Walk the list from the header and check the order
+        let list_header = get_list_header(&mut banks_client, list_header_account.pubkey()).await;
+        assert_eq!(list_header.len, 5);
+        assert_eq!(list_header.head, node_accounts[0]);
+        assert_eq!(list_header.tail, node_accounts[4]);
+        let mut current = list_header.head;
+        for (i, node_account) in node_accounts.iter().enumerate() {
+            assert_eq!(current, *node_account);
+            let node = get_node(&mut banks_client, current).await;
+            assert_eq!(node.data, vec![i as u8 + 1]);
+            current = node.next;
+        }
+        assert_eq!(current, list_header.head);
+
         for i in (1..=5).rev() {
-            let target_account_data = banks_client
-                .get_account(node_accounts[i - 1].pubkey())
-                .await
-                .unwrap()
-                .unwrap()
-                .data;
-            let target_node = Node::try_from_slice(&target_account_data).unwrap();
-            let remove_node_instruction = Instruction {
-                program_id,
-                accounts: vec![
-                    AccountMeta::new(payer.pubkey(), true),
-                    AccountMeta::new(head_account.pubkey(), false),
-                    AccountMeta::new(node_accounts[i - 1].pubkey(), false),
-                    AccountMeta::new(target_node.prev, false),
-                    AccountMeta::new(target_node.next, false),
-                ],
-                data: InstructionData::RemoveNode {
-                    target_node: node_accounts[i - 1].pubkey(),
-                }
-                .try_to_vec()
-                .unwrap(),
-            };
+            let target_node = get_node(&mut banks_client, node_accounts[i - 1]).await;
+            let remove_node_instruction = instruction::remove_node(
+                &program_id,
+                &payer.pubkey(),
+                &list_header_account.pubkey(),
+                &node_accounts[i - 1],
+                &payer.pubkey(),
+                &[target_node.prev, target_node.next],
+            );
 
             let transaction = Transaction::new_signed_with_payer(
                 &[remove_node_instruction],
@@ -568,23 +689,2660 @@ mod tests {
             banks_client.process_transaction(transaction).await.unwrap();
 
             let node_account_data = banks_client
-                .get_account(node_accounts[i - 1].pubkey())
+                .get_account(node_accounts[i - 1])
                 .await
                 .unwrap();
 
             assert!(node_account_data.is_none());
         }
 
-        let head_account_data = banks_client
-            .get_account(head_account.pubkey())
+        let list_header = get_list_header(&mut banks_client, list_header_account.pubkey()).await;
+
+        assert_eq!(list_header.head, Pubkey::default());
+        assert_eq!(list_header.tail, Pubkey::default());
+        assert_eq!(list_header.len, 0);
+    }
+
+    #[test]
+    fn test_error_codes() {
+        let codes = [
+            (CdllError::ListAlreadyInitialized, 0),
+            (CdllError::ListNotInitialized, 1),
+            (CdllError::IncorrectOwner, 2),
+            (CdllError::MissingSigner, 3),
+            (CdllError::InvalidNodeAddress, 4),
+            (CdllError::NodeNotInList, 5),
+            (CdllError::HeadAccountMissing, 6),
+            (CdllError::TailAccountMissing, 7),
+            (CdllError::PrevAccountMissing, 8),
+            (CdllError::NextAccountMissing, 9),
+            (CdllError::ListEmpty, 10),
+            (CdllError::ListFull, 11),
+            (CdllError::NodeClosed, 12),
+            (CdllError::InvalidAuthority, 13),
+            (CdllError::ListImmutable, 14),
+            (CdllError::AnchorAccountMissing, 15),
+            (CdllError::BrokenLink, 16),
+            (CdllError::InvalidTargetNode, 17),
+            (CdllError::DataTooLarge, 18),
+            (CdllError::DataMismatch, 19),
+            (CdllError::LengthMismatch, 20),
+            (CdllError::HeadMismatch, 21),
+            (CdllError::TailMismatch, 22),
+            (CdllError::InvalidAccountType, 23),
+            (CdllError::UnsupportedVersion, 24),
+        ];
+
+        for (error, code) in codes {
+            assert_eq!(ProgramError::from(error), ProgramError::Custom(code));
+            assert_eq!(
+                <CdllError as DecodeError<CdllError>>::decode_custom_error_to_enum(code),
+                Some(error)
+            );
+        }
+        assert_eq!(
+            <CdllError as DecodeError<CdllError>>::decode_custom_error_to_enum(codes.len() as u32),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_instruction_errors() {
+        let program_id = Pubkey::new_unique();
+        let program_test = ProgramTest::new(
+            "circular_doubly_ll_solana",
+            program_id,
+            processor!(process_instruction),
+        );
+
+        let list_a = Keypair::new();
+        let list_b = Keypair::new();
+
+        let ProgramTestContext {
+            mut banks_client,
+            last_blockhash,
+            payer,
+            ..
+        } = program_test.start_with_context().await;
+
+        let transaction = Transaction::new_signed_with_payer(
+            &[
+                initialize_list_instruction(program_id, &payer, &list_a),
+                initialize_list_instruction(program_id, &payer, &list_b),
+            ],
+            Some(&payer.pubkey()),
+            &[&payer, &list_a, &list_b],
+            last_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        let (node_a0, _) = find_node_address(&program_id, &list_a.pubkey(), 0);
+        let (node_a1, _) = find_node_address(&program_id, &list_a.pubkey(), 1);
+        let (node_b0, _) = find_node_address(&program_id, &list_b.pubkey(), 0);
+
+        let add_node = |list: Pubkey, new_node: Pubkey, neighbors: &[Pubkey]| {
+            instruction::add_node(
+                &program_id,
+                &payer.pubkey(),
+                &payer.pubkey(),
+                &list,
+                &new_node,
+                neighbors,
+                vec![7],
+            )
+        };
+        let remove_node = |list: Pubkey, target: Pubkey| {
+            instruction::remove_node(
+                &program_id,
+                &payer.pubkey(),
+                &list,
+                &target,
+                &payer.pubkey(),
+                &[target],
+            )
+        };
+        let send = |instruction: Instruction| {
+            Transaction::new_signed_with_payer(
+                &[instruction],
+                Some(&payer.pubkey()),
+                &[&payer],
+                last_blockhash,
+            )
+        };
+
+        // Removing from an empty list
+        assert_cdll_error(
+            banks_client
+                .process_transaction(send(remove_node(list_a.pubkey(), node_a0)))
+                .await,
+            CdllError::ListEmpty,
+        );
+
+        // New node address is not the next PDA of the list
+        assert_cdll_error(
+            banks_client
+                .process_transaction(send(add_node(list_a.pubkey(), node_b0, &[])))
+                .await,
+            CdllError::InvalidNodeAddress,
+        );
+
+        for (list, node) in [(list_a.pubkey(), node_a0), (list_b.pubkey(), node_b0)] {
+            banks_client
+                .process_transaction(send(add_node(list, node, &[])))
+                .await
+                .unwrap();
+        }
+
+        // Tail account not supplied
+        assert_cdll_error(
+            banks_client
+                .process_transaction(send(add_node(list_a.pubkey(), node_a1, &[])))
+                .await,
+            CdllError::TailAccountMissing,
+        );
+
+        // Node from another list
+        assert_cdll_error(
+            banks_client
+                .process_transaction(send(remove_node(list_b.pubkey(), node_a0)))
+                .await,
+            CdllError::NodeNotInList,
+        );
+
+        // A list header passed as a node
+        assert_cdll_error(
+            banks_client
+                .process_transaction(send(remove_node(list_b.pubkey(), list_a.pubkey())))
+                .await,
+            CdllError::InvalidAccountType,
+        );
+
+        // A node passed as a list header
+        assert_cdll_error(
+            banks_client
+                .process_transaction(send(remove_node(node_a0, node_b0)))
+                .await,
+            CdllError::InvalidAccountType,
+        );
+    }
+
+    #[tokio::test]
+    async fn test_set_authority() {
+        let program_id = Pubkey::new_unique();
+        let program_test = ProgramTest::new(
+            "circular_doubly_ll_solana",
+            program_id,
+            processor!(process_instruction),
+        );
+
+        let list_header_account = Keypair::new();
+        let new_authority = Keypair::new();
+
+        let ProgramTestContext {
+            mut banks_client,
+            last_blockhash,
+            payer,
+            ..
+        } = program_test.start_with_context().await;
+
+        let transaction = Transaction::new_signed_with_payer(
+            &[initialize_list_instruction(
+                program_id,
+                &payer,
+                &list_header_account,
+            )],
+            Some(&payer.pubkey()),
+            &[&payer, &list_header_account],
+            last_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        let add_node = |authority: &Keypair, seq: u64, neighbors: &[Pubkey]| {
+            let (new_node, _) = find_node_address(&program_id, &list_header_account.pubkey(), seq);
+            let instruction = instruction::add_node(
+                &program_id,
+                &authority.pubkey(),
+                &payer.pubkey(),
+                &list_header_account.pubkey(),
+                &new_node,
+                neighbors,
+                vec![seq as u8],
+            );
+            Transaction::new_signed_with_payer(
+                &[instruction],
+                Some(&payer.pubkey()),
+                &[&payer, authority],
+                last_blockhash,
+            )
+        };
+        let set_authority = |authority: &Keypair, new_authority: Option<&Pubkey>| {
+            let instruction = instruction::set_authority(
+                &program_id,
+                &authority.pubkey(),
+                &list_header_account.pubkey(),
+                new_authority,
+            );
+            Transaction::new_signed_with_payer(
+                &[instruction],
+                Some(&payer.pubkey()),
+                &[&payer, authority],
+                last_blockhash,
+            )
+        };
+
+        // Only the authority may mutate the list
+        assert_cdll_error(
+            banks_client
+                .process_transaction(add_node(&new_authority, 0, &[]))
+                .await,
+            CdllError::InvalidAuthority,
+        );
+
+        // The authority has to sign; built by hand since the builder marks it
+        // as a signer
+        let (node0, _) = find_node_address(&program_id, &list_header_account.pubkey(), 0);
+        let unsigned_add_node = Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new_readonly(list_header_account.pubkey(), false),
+                AccountMeta::new(payer.pubkey(), true),
+                AccountMeta::new(list_header_account.pubkey(), false),
+                AccountMeta::new(node0, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+            data: InstructionData::AddNode { data: vec![0] }
+                .try_to_vec()
+                .unwrap(),
+        };
+        assert_cdll_error(
+            banks_client
+                .process_transaction(Transaction::new_signed_with_payer(
+                    &[unsigned_add_node],
+                    Some(&payer.pubkey()),
+                    &[&payer],
+                    last_blockhash,
+                ))
+                .await,
+            CdllError::MissingSigner,
+        );
+
+        banks_client
+            .process_transaction(add_node(&payer, 0, &[]))
             .await
-            .unwrap()
-            .unwrap()
-            .data;
+            .unwrap();
+
+        // Transfer the authority
+        banks_client
+            .process_transaction(set_authority(&payer, Some(&new_authority.pubkey())))
+            .await
+            .unwrap();
+        let list_header = get_list_header(&mut banks_client, list_header_account.pubkey()).await;
+        assert_eq!(list_header.authority, new_authority.pubkey());
+
+        assert_cdll_error(
+            banks_client
+                .process_transaction(add_node(&payer, 1, &[node0]))
+                .await,
+            CdllError::InvalidAuthority,
+        );
+        banks_client
+            .process_transaction(add_node(&new_authority, 1, &[node0]))
+            .await
+            .unwrap();
+
+        // Renounce the authority
+        banks_client
+            .process_transaction(set_authority(&new_authority, None))
+            .await
+            .unwrap();
+        let list_header = get_list_header(&mut banks_client, list_header_account.pubkey()).await;
+        assert_eq!(list_header.authority, Pubkey::default());
+        assert_eq!(list_header.len, 2);
+
+        let (node1, _) = find_node_address(&program_id, &list_header_account.pubkey(), 1);
+        assert_cdll_error(
+            banks_client
+                .process_transaction(add_node(&new_authority, 2, &[node0, node1]))
+                .await,
+            CdllError::ListImmutable,
+        );
+        assert_cdll_error(
+            banks_client
+                .process_transaction(set_authority(&new_authority, Some(&payer.pubkey())))
+                .await,
+            CdllError::ListImmutable,
+        );
+    }
+
+    #[tokio::test]
+    async fn test_insert_after_and_before() {
+        let program_id = Pubkey::new_unique();
+        let program_test = ProgramTest::new(
+            "circular_doubly_ll_solana",
+            program_id,
+            processor!(process_instruction),
+        );
+
+        let list_header_account = Keypair::new();
+
+        let ProgramTestContext {
+            mut banks_client,
+            last_blockhash,
+            payer,
+            ..
+        } = program_test.start_with_context().await;
+
+        let transaction = Transaction::new_signed_with_payer(
+            &[initialize_list_instruction(
+                program_id,
+                &payer,
+                &list_header_account,
+            )],
+            Some(&payer.pubkey()),
+            &[&payer, &list_header_account],
+            last_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        let list = list_header_account.pubkey();
+        let node = |seq: u64| find_node_address(&program_id, &list, seq).0;
+        let send = |instruction: Instruction| {
+            Transaction::new_signed_with_payer(
+                &[instruction],
+                Some(&payer.pubkey()),
+                &[&payer],
+                last_blockhash,
+            )
+        };
+        // Node `seq` carries `seq` as its payload
+        let insert_after = |seq: u64, anchor: Pubkey, anchor_next: Pubkey| {
+            send(instruction::insert_after(
+                &program_id,
+                &payer.pubkey(),
+                &payer.pubkey(),
+                &list,
+                &node(seq),
+                &anchor,
+                &anchor_next,
+                vec![seq as u8],
+            ))
+        };
+        let insert_before = |seq: u64, anchor: Pubkey, anchor_prev: Pubkey| {
+            send(instruction::insert_before(
+                &program_id,
+                &payer.pubkey(),
+                &payer.pubkey(),
+                &list,
+                &node(seq),
+                &anchor,
+                &anchor_prev,
+                vec![seq as u8],
+            ))
+        };
+
+        // There is nothing to anchor to in an empty list
+        assert_cdll_error(
+            banks_client
+                .process_transaction(insert_after(0, node(0), node(0)))
+                .await,
+            CdllError::ListEmpty,
+        );
+
+        // [0]
+        banks_client
+            .process_transaction(send(instruction::add_node(
+                &program_id,
+                &payer.pubkey(),
+                &payer.pubkey(),
+                &list,
+                &node(0),
+                &[],
+                vec![0],
+            )))
+            .await
+            .unwrap();
+
+        // [0, 1]: inserting after the tail moves the tail
+        banks_client
+            .process_transaction(insert_after(1, node(0), node(0)))
+            .await
+            .unwrap();
+
+        // [2, 0, 1]: inserting before the head moves the head
+        banks_client
+            .process_transaction(insert_before(2, node(0), node(1)))
+            .await
+            .unwrap();
+
+        // The anchor's neighbor has to be supplied
+        assert_cdll_error(
+            banks_client
+                .process_transaction(insert_after(3, node(0), node(2)))
+                .await,
+            CdllError::NextAccountMissing,
+        );
+
+        // [2, 0, 3, 1]
+        banks_client
+            .process_transaction(insert_after(3, node(0), node(1)))
+            .await
+            .unwrap();
+
+        // [2, 0, 3, 4, 1]
+        banks_client
+            .process_transaction(insert_before(4, node(1), node(3)))
+            .await
+            .unwrap();
+
+        let list_header = get_list_header(&mut banks_client, list_header_account.pubkey()).await;
+        assert_eq!(list_header.len, 5);
+        assert_eq!(list_header.head, node(2));
+        assert_eq!(list_header.tail, node(1));
+
+        let nodes = walk_list(&mut banks_client, list_header_account.pubkey()).await;
+        assert_eq!(
+            nodes,
+            [2, 0, 3, 4, 1]
+                .into_iter()
+                .map(|seq| (node(seq), vec![seq as u8]))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_deque_operations() {
+        let program_id = Pubkey::new_unique();
+        let program_test = ProgramTest::new(
+            "circular_doubly_ll_solana",
+            program_id,
+            processor!(process_instruction),
+        );
+
+        let list_header_account = Keypair::new();
+
+        let ProgramTestContext {
+            mut banks_client,
+            last_blockhash,
+            payer,
+            ..
+        } = program_test.start_with_context().await;
+
+        let transaction = Transaction::new_signed_with_payer(
+            &[initialize_list_instruction(
+                program_id,
+                &payer,
+                &list_header_account,
+            )],
+            Some(&payer.pubkey()),
+            &[&payer, &list_header_account],
+            last_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        let list = list_header_account.pubkey();
+        let node = |seq: u64| find_node_address(&program_id, &list, seq).0;
+        let send = |instruction: Instruction| {
+            Transaction::new_signed_with_payer(
+                &[instruction],
+                Some(&payer.pubkey()),
+                &[&payer],
+                last_blockhash,
+            )
+        };
+        let authority = payer.pubkey();
+        let push_back = |seq: u64, neighbors: &[Pubkey], data: Vec<u8>| {
+            send(instruction::push_back(
+                &program_id,
+                &authority,
+                &authority,
+                &list,
+                &node(seq),
+                neighbors,
+                data,
+            ))
+        };
+        let push_front = |seq: u64, neighbors: &[Pubkey], data: Vec<u8>| {
+            send(instruction::push_front(
+                &program_id,
+                &authority,
+                &authority,
+                &list,
+                &node(seq),
+                neighbors,
+                data,
+            ))
+        };
+        let pop_front = |head: Pubkey, neighbors: &[Pubkey]| {
+            send(instruction::pop_front(
+                &program_id,
+                &authority,
+                &list,
+                &head,
+                &authority,
+                neighbors,
+            ))
+        };
+        let pop_back = |tail: Pubkey, neighbors: &[Pubkey]| {
+            send(instruction::pop_back(
+                &program_id,
+                &authority,
+                &list,
+                &tail,
+                &authority,
+                neighbors,
+            ))
+        };
+
+        assert_cdll_error(
+            banks_client
+                .process_transaction(pop_front(node(0), &[]))
+                .await,
+            CdllError::ListEmpty,
+        );
+
+        // [1] -> [1, 2] -> [0, 1, 2]
+        banks_client
+            .process_transaction(push_back(0, &[], vec![1]))
+            .await
+            .unwrap();
+        banks_client
+            .process_transaction(push_back(1, &[node(0)], vec![2]))
+            .await
+            .unwrap();
+        banks_client
+            .process_transaction(push_front(2, &[node(0), node(1)], vec![0]))
+            .await
+            .unwrap();
+
+        let nodes = walk_list(&mut banks_client, list_header_account.pubkey()).await;
+        assert_eq!(
+            nodes,
+            vec![(node(2), vec![0]), (node(0), vec![1]), (node(1), vec![2])]
+        );
+
+        // Popping anything other than the head is rejected
+        assert_cdll_error(
+            banks_client
+                .process_transaction(pop_front(node(0), &[node(2), node(1)]))
+                .await,
+            CdllError::InvalidTargetNode,
+        );
+
+        let result = banks_client
+            .process_transaction_with_metadata(pop_front(node(2), &[node(1), node(0)]))
+            .await
+            .unwrap();
+        assert!(result.result.is_ok());
+        let return_data = result.metadata.unwrap().return_data.unwrap();
+        assert_eq!(return_data.program_id, program_id);
+        assert_eq!(return_data.data, vec![0]);
+
+        let result = banks_client
+            .process_transaction_with_metadata(pop_back(node(1), &[node(0)]))
+            .await
+            .unwrap();
+        assert!(result.result.is_ok());
+        let return_data = result.metadata.unwrap().return_data.unwrap();
+        assert_eq!(return_data.data, vec![2]);
+
+        let nodes = walk_list(&mut banks_client, list_header_account.pubkey()).await;
+        assert_eq!(nodes, vec![(node(0), vec![1])]);
+        assert!(banks_client.get_account(node(2)).await.unwrap().is_none());
+        assert!(banks_client.get_account(node(1)).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_update_node() {
+        let program_id = Pubkey::new_unique();
+        let program_test = ProgramTest::new(
+            "circular_doubly_ll_solana",
+            program_id,
+            processor!(process_instruction),
+        );
+
+        let list_header_account = Keypair::new();
+
+        let ProgramTestContext {
+            mut banks_client,
+            last_blockhash,
+            payer,
+            ..
+        } = program_test.start_with_context().await;
+
+        // The configured maximum is itself capped
+        let list = list_header_account.pubkey();
+        let authority = payer.pubkey();
+        let oversized_list =
+            instruction::initialize_list(&program_id, &authority, &list, MAX_NODE_DATA_LEN + 1);
+        assert_cdll_error(
+            banks_client
+                .process_transaction(Transaction::new_signed_with_payer(
+                    &[oversized_list],
+                    Some(&payer.pubkey()),
+                    &[&payer, &list_header_account],
+                    last_blockhash,
+                ))
+                .await,
+            CdllError::DataTooLarge,
+        );
+
+        let transaction = Transaction::new_signed_with_payer(
+            &[initialize_list_instruction(
+                program_id,
+                &payer,
+                &list_header_account,
+            )],
+            Some(&payer.pubkey()),
+            &[&payer, &list_header_account],
+            last_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        let (node0, _) = find_node_address(&program_id, &list, 0);
+        let send = |instruction: Instruction| {
+            Transaction::new_signed_with_payer(
+                &[instruction],
+                Some(&payer.pubkey()),
+                &[&payer],
+                last_blockhash,
+            )
+        };
+        let add_node = |data: Vec<u8>| {
+            send(instruction::add_node(
+                &program_id,
+                &authority,
+                &authority,
+                &list,
+                &node0,
+                &[],
+                data,
+            ))
+        };
+        let update_node = |data: Vec<u8>| {
+            send(instruction::update_node(
+                &program_id,
+                &authority,
+                &authority,
+                &list,
+                &node0,
+                data,
+            ))
+        };
+        let update_node_if_equals = |expected: Vec<u8>, new: Vec<u8>| {
+            send(instruction::update_node_if_equals(
+                &program_id,
+                &authority,
+                &authority,
+                &list,
+                &node0,
+                expected,
+                new,
+            ))
+        };
+
+        assert_cdll_error(
+            banks_client
+                .process_transaction(add_node(vec![1; MAX_DATA_LEN as usize + 1]))
+                .await,
+            CdllError::DataTooLarge,
+        );
+        banks_client
+            .process_transaction(add_node(vec![1; 4]))
+            .await
+            .unwrap();
+
+        let rent = banks_client.get_rent().await.unwrap();
+        let small_account = banks_client.get_account(node0).await.unwrap().unwrap();
+        assert_eq!(
+            small_account.lamports,
+            rent.minimum_balance(small_account.data.len())
+        );
+
+        // Growing the payload reallocs the account and tops up its rent
+        banks_client
+            .process_transaction(update_node(vec![2; MAX_DATA_LEN as usize]))
+            .await
+            .unwrap();
+        let large_account = banks_client.get_account(node0).await.unwrap().unwrap();
+        assert_eq!(
+            large_account.data.len(),
+            small_account.data.len() + MAX_DATA_LEN as usize - 4
+        );
+        assert_eq!(
+            large_account.lamports,
+            rent.minimum_balance(large_account.data.len())
+        );
+        let node = Node::try_from_slice(&large_account.data).unwrap();
+        assert_eq!(node.data, vec![2; MAX_DATA_LEN as usize]);
+        assert_eq!(node.prev, node0);
+        assert_eq!(node.next, node0);
+
+        assert_cdll_error(
+            banks_client
+                .process_transaction(update_node(vec![3; MAX_DATA_LEN as usize + 1]))
+                .await,
+            CdllError::DataTooLarge,
+        );
+
+        // Shrinking it refunds the excess
+        banks_client
+            .process_transaction(update_node(vec![]))
+            .await
+            .unwrap();
+        let empty_account = banks_client.get_account(node0).await.unwrap().unwrap();
+        assert_eq!(empty_account.data.len(), small_account.data.len() - 4);
+        assert_eq!(
+            empty_account.lamports,
+            rent.minimum_balance(empty_account.data.len())
+        );
+        assert!(Node::try_from_slice(&empty_account.data)
+            .unwrap()
+            .data
+            .is_empty());
+
+        // The node argument has to match the node account
+        let mut mismatched_node =
+            instruction::update_node(&program_id, &authority, &authority, &list, &node0, vec![4]);
+        mismatched_node.data = InstructionData::UpdateNode {
+            node: list,
+            data: vec![4],
+        }
+        .try_to_vec()
+        .unwrap();
+        assert_cdll_error(
+            banks_client
+                .process_transaction(send(mismatched_node))
+                .await,
+            CdllError::InvalidTargetNode,
+        );
+
+        // Compare-and-swap only applies when the current payload matches
+        assert_cdll_error(
+            banks_client
+                .process_transaction(update_node_if_equals(
+                    vec![2; MAX_DATA_LEN as usize],
+                    vec![5],
+                ))
+                .await,
+            CdllError::DataMismatch,
+        );
+        banks_client
+            .process_transaction(update_node_if_equals(vec![], vec![6]))
+            .await
+            .unwrap();
+        assert_eq!(get_node(&mut banks_client, node0).await.data, vec![6]);
+    }
+
+    #[tokio::test]
+    async fn test_verify_list() {
+        let program_id = Pubkey::new_unique();
+        let mut program_test = ProgramTest::new(
+            "circular_doubly_ll_solana",
+            program_id,
+            processor!(process_instruction),
+        );
+
+        // A list whose third node has a stale prev pointer
+        let (corrupted_list, corrupted_keys) = add_list_accounts(
+            &mut program_test,
+            program_id,
+            Pubkey::new_unique(),
+            3,
+            |i, nodes| match i {
+                // prev should be node 1
+                2 => (nodes[0], nodes[0]),
+                _ => (nodes[(i + 2) % 3], nodes[(i + 1) % 3]),
+            },
+        );
+
+        // Empty lists whose header still points at a head or a tail
+        let mut add_empty_list = |head: Pubkey, tail: Pubkey| {
+            let list = Pubkey::new_unique();
+            let header = ListHeader {
+                discriminator: ListHeader::DISCRIMINATOR,
+                version: LIST_HEADER_VERSION,
+                authority: Pubkey::new_unique(),
+                head,
+                tail,
+                len: 0,
+                next_seq: 3,
+                max_data_len: MAX_DATA_LEN,
+            };
+            let data = borsh::to_vec(&header).unwrap();
+            program_test.add_account(
+                list,
+                Account {
+                    lamports: Rent::default().minimum_balance(data.len()),
+                    data,
+                    owner: program_id,
+                    executable: false,
+                    rent_epoch: 0,
+                },
+            );
+            list
+        };
+        let stale_head_list = add_empty_list(corrupted_keys[0], Pubkey::default());
+        let stale_tail_list = add_empty_list(Pubkey::default(), corrupted_keys[2]);
+
+        let list_header_account = Keypair::new();
+
+        let ProgramTestContext {
+            mut banks_client,
+            last_blockhash,
+            payer,
+            ..
+        } = program_test.start_with_context().await;
+
+        let transaction = Transaction::new_signed_with_payer(
+            &[initialize_list_instruction(
+                program_id,
+                &payer,
+                &list_header_account,
+            )],
+            Some(&payer.pubkey()),
+            &[&payer, &list_header_account],
+            last_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        let node = |seq: u64| find_node_address(&program_id, &list_header_account.pubkey(), seq).0;
+        let verify_list = |list: Pubkey, nodes: &[Pubkey]| {
+            let instruction = instruction::verify_list(&program_id, &list, nodes);
+            Transaction::new_signed_with_payer(
+                &[instruction],
+                Some(&payer.pubkey()),
+                &[&payer],
+                last_blockhash,
+            )
+        };
+
+        // An empty list verifies without any nodes
+        banks_client
+            .process_transaction(verify_list(list_header_account.pubkey(), &[]))
+            .await
+            .unwrap();
+
+        for seq in 0..4 {
+            let neighbors = if seq > 0 {
+                vec![node(seq - 1), node(0)]
+            } else {
+                vec![]
+            };
+            let instruction = instruction::push_back(
+                &program_id,
+                &payer.pubkey(),
+                &payer.pubkey(),
+                &list_header_account.pubkey(),
+                &node(seq),
+                &neighbors,
+                vec![seq as u8],
+            );
+            banks_client
+                .process_transaction(Transaction::new_signed_with_payer(
+                    &[instruction],
+                    Some(&payer.pubkey()),
+                    &[&payer],
+                    last_blockhash,
+                ))
+                .await
+                .unwrap();
+        }
+
+        banks_client
+            .process_transaction(verify_list(
+                list_header_account.pubkey(),
+                &[node(0), node(1), node(2), node(3)],
+            ))
+            .await
+            .unwrap();
+
+        assert_cdll_error(
+            banks_client
+                .process_transaction(verify_list(
+                    list_header_account.pubkey(),
+                    &[node(0), node(1), node(2)],
+                ))
+                .await,
+            CdllError::LengthMismatch,
+        );
+        assert_cdll_error(
+            banks_client
+                .process_transaction(verify_list(
+                    list_header_account.pubkey(),
+                    &[node(1), node(2), node(3), node(0)],
+                ))
+                .await,
+            CdllError::HeadMismatch,
+        );
+        assert_cdll_error(
+            banks_client
+                .process_transaction(verify_list(
+                    list_header_account.pubkey(),
+                    &[node(0), node(1), node(3), node(2)],
+                ))
+                .await,
+            CdllError::TailMismatch,
+        );
+        assert_cdll_error(
+            banks_client
+                .process_transaction(verify_list(
+                    list_header_account.pubkey(),
+                    &[node(0), node(2), node(1), node(3)],
+                ))
+                .await,
+            CdllError::BrokenLink,
+        );
+
+        assert_cdll_error(
+            banks_client
+                .process_transaction(verify_list(stale_head_list, &[]))
+                .await,
+            CdllError::HeadMismatch,
+        );
+        assert_cdll_error(
+            banks_client
+                .process_transaction(verify_list(stale_tail_list, &[]))
+                .await,
+            CdllError::TailMismatch,
+        );
+
+        // The corrupted list fails at its first broken link
+        let result = banks_client
+            .process_transaction_with_metadata(verify_list(corrupted_list, &corrupted_keys))
+            .await
+            .unwrap();
+        assert_eq!(
+            result.result.unwrap_err(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(CdllError::BrokenLink as u32)
+            )
+        );
+        assert!(result
+            .metadata
+            .unwrap()
+            .log_messages
+            .iter()
+            .any(|log| log.ends_with("Node 2.prev does not point at node 1")));
+    }
+
+    #[tokio::test]
+    async fn test_remove_node_validation() {
+        let program_id = Pubkey::new_unique();
+        let mut program_test = ProgramTest::new(
+            "circular_doubly_ll_solana",
+            program_id,
+            processor!(process_instruction),
+        );
+
+        let authority = Keypair::new();
+
+        // An account of some other program
+        let foreign_account = Pubkey::new_unique();
+        program_test.add_account(
+            foreign_account,
+            Account {
+                lamports: Rent::default().minimum_balance(8),
+                data: vec![0; 8],
+                owner: Pubkey::new_unique(),
+                executable: false,
+                rent_epoch: 0,
+            },
+        );
+
+        // A list header written by an older layout version
+        let old_list = Pubkey::new_unique();
+        let mut old_list_data = ListHeader::DISCRIMINATOR.to_vec();
+        old_list_data.push(LIST_HEADER_VERSION - 1);
+        old_list_data.resize(128, 0);
+        program_test.add_account(
+            old_list,
+            Account {
+                lamports: Rent::default().minimum_balance(old_list_data.len()),
+                data: old_list_data,
+                owner: program_id,
+                executable: false,
+                rent_epoch: 0,
+            },
+        );
+
+        // A healthy list
+        let (list, nodes) = add_list_accounts(
+            &mut program_test,
+            program_id,
+            authority.pubkey(),
+            3,
+            |i, nodes| (nodes[(i + 2) % 3], nodes[(i + 1) % 3]),
+        );
+        // Node 1 claims a foreign account as its prev
+        let (foreign_list, foreign_nodes) = add_list_accounts(
+            &mut program_test,
+            program_id,
+            authority.pubkey(),
+            3,
+            |i, nodes| match i {
+                1 => (foreign_account, nodes[2]),
+                _ => (nodes[(i + 2) % 3], nodes[(i + 1) % 3]),
+            },
+        );
+        // Node 2 still points back at node 0
+        let (stale_list, stale_nodes) = add_list_accounts(
+            &mut program_test,
+            program_id,
+            authority.pubkey(),
+            3,
+            |i, nodes| match i {
+                2 => (nodes[0], nodes[0]),
+                _ => (nodes[(i + 2) % 3], nodes[(i + 1) % 3]),
+            },
+        );
+
+        let ProgramTestContext {
+            mut banks_client,
+            last_blockhash,
+            payer,
+            ..
+        } = program_test.start_with_context().await;
+
+        let remove_node = |list: Pubkey, target_node: Pubkey, accounts: &[Pubkey]| {
+            let mut metas = vec![
+                AccountMeta::new_readonly(authority.pubkey(), true),
+                AccountMeta::new(list, false),
+            ];
+            metas.extend(accounts.iter().map(|key| AccountMeta::new(*key, false)));
+            metas.insert(3, AccountMeta::new(payer.pubkey(), false));
+            let instruction = Instruction {
+                program_id,
+                accounts: metas,
+                data: InstructionData::RemoveNode { target_node }
+                    .try_to_vec()
+                    .unwrap(),
+            };
+            Transaction::new_signed_with_payer(
+                &[instruction],
+                Some(&payer.pubkey()),
+                &[&payer, &authority],
+                last_blockhash,
+            )
+        };
+
+        // Accounts of an unknown layout version are rejected
+        assert_cdll_error(
+            banks_client
+                .process_transaction(remove_node(
+                    old_list,
+                    nodes[1],
+                    &[nodes[1], nodes[0], nodes[2]],
+                ))
+                .await,
+            CdllError::UnsupportedVersion,
+        );
+
+        // The instruction argument has to name the target account
+        assert_cdll_error(
+            banks_client
+                .process_transaction(remove_node(list, nodes[2], &[nodes[1], nodes[0], nodes[2]]))
+                .await,
+            CdllError::InvalidTargetNode,
+        );
+
+        // Every touched node has to be owned by the program
+        assert_cdll_error(
+            banks_client
+                .process_transaction(remove_node(
+                    foreign_list,
+                    foreign_nodes[1],
+                    &[foreign_nodes[1], foreign_account, foreign_nodes[2]],
+                ))
+                .await,
+            CdllError::IncorrectOwner,
+        );
+
+        // The neighbors have to link back to the target
+        assert_cdll_error(
+            banks_client
+                .process_transaction(remove_node(
+                    stale_list,
+                    stale_nodes[1],
+                    &[stale_nodes[1], stale_nodes[0], stale_nodes[2]],
+                ))
+                .await,
+            CdllError::BrokenLink,
+        );
+
+        // The healthy list still allows the removal
+        banks_client
+            .process_transaction(remove_node(list, nodes[1], &[nodes[1], nodes[0], nodes[2]]))
+            .await
+            .unwrap();
+        let nodes_left = walk_list(&mut banks_client, list).await;
+        assert_eq!(nodes_left, vec![(nodes[0], vec![0]), (nodes[2], vec![2])]);
+    }
+
+    #[tokio::test]
+    async fn test_clear_list() {
+        let program_id = Pubkey::new_unique();
+        let mut program_test = ProgramTest::new(
+            "circular_doubly_ll_solana",
+            program_id,
+            processor!(process_instruction),
+        );
+
+        let authority = Keypair::new();
+        let (list, nodes) = add_list_accounts(
+            &mut program_test,
+            program_id,
+            authority.pubkey(),
+            5,
+            |i, nodes| (nodes[(i + 4) % 5], nodes[(i + 1) % 5]),
+        );
+
+        let ProgramTestContext {
+            mut banks_client,
+            last_blockhash,
+            payer,
+            ..
+        } = program_test.start_with_context().await;
+
+        let rent_receiver = Pubkey::new_unique();
+        let clear_list = |max_nodes: u32, accounts: &[Pubkey]| {
+            let mut metas = vec![
+                AccountMeta::new_readonly(authority.pubkey(), true),
+                AccountMeta::new(list, false),
+                AccountMeta::new(rent_receiver, false),
+            ];
+            metas.extend(accounts.iter().map(|key| AccountMeta::new(*key, false)));
+            let instruction = Instruction {
+                program_id,
+                accounts: metas,
+                data: InstructionData::ClearList { max_nodes }
+                    .try_to_vec()
+                    .unwrap(),
+            };
+            Transaction::new_signed_with_payer(
+                &[instruction],
+                Some(&payer.pubkey()),
+                &[&payer, &authority],
+                last_blockhash,
+            )
+        };
+
+        // The batch has to start at the head
+        assert_cdll_error(
+            banks_client
+                .process_transaction(clear_list(2, &[nodes[1], nodes[2], nodes[4]]))
+                .await,
+            CdllError::HeadAccountMissing,
+        );
+        // A partial clear has to relink the tail
+        assert_cdll_error(
+            banks_client
+                .process_transaction(clear_list(2, &[nodes[0], nodes[1], nodes[2]]))
+                .await,
+            CdllError::TailAccountMissing,
+        );
+        // Clearing nothing needs no nodes
+        banks_client
+            .process_transaction(clear_list(0, &[]))
+            .await
+            .unwrap();
+        assert_eq!(get_list_header(&mut banks_client, list).await.len, 5);
+
+        let mut node_lamports = 0;
+        for node in &nodes {
+            node_lamports += banks_client.get_balance(*node).await.unwrap();
+        }
+
+        // First batch: two nodes
+        banks_client
+            .process_transaction(clear_list(2, &[nodes[0], nodes[1], nodes[2], nodes[4]]))
+            .await
+            .unwrap();
+        let list_header = get_list_header(&mut banks_client, list).await;
+        assert_eq!(list_header.len, 3);
+        assert_eq!(
+            walk_list(&mut banks_client, list).await,
+            vec![
+                (nodes[2], vec![2]),
+                (nodes[3], vec![3]),
+                (nodes[4], vec![4])
+            ]
+        );
+        for node in &nodes[..2] {
+            assert!(banks_client.get_account(*node).await.unwrap().is_none());
+        }
+
+        // Second batch: the rest
+        banks_client
+            .process_transaction(clear_list(10, &[nodes[2], nodes[3], nodes[4]]))
+            .await
+            .unwrap();
+        let list_header = get_list_header(&mut banks_client, list).await;
+        assert!(list_header.is_empty());
+        assert_eq!(list_header.head, Pubkey::default());
+        assert_eq!(list_header.tail, Pubkey::default());
+        assert_eq!(list_header.next_seq, 5);
+
+        // All of the rent went to the receiver
+        assert_eq!(
+            banks_client.get_balance(rent_receiver).await.unwrap(),
+            node_lamports
+        );
+
+        // Clearing an empty list is a no-op
+        banks_client
+            .process_transaction(clear_list(10, &[]))
+            .await
+            .unwrap();
+    }
+
+    #[test]
+    fn test_account_sizes() {
+        let header = ListHeader {
+            discriminator: ListHeader::DISCRIMINATOR,
+            version: LIST_HEADER_VERSION,
+            authority: Pubkey::new_unique(),
+            head: Pubkey::new_unique(),
+            tail: Pubkey::new_unique(),
+            len: u64::MAX,
+            next_seq: u64::MAX,
+            max_data_len: MAX_NODE_DATA_LEN,
+        };
+        assert_eq!(borsh::to_vec(&header).unwrap().len(), ListHeader::LEN);
+
+        for data_len in [0, 1, MAX_NODE_DATA_LEN as usize] {
+            let node = Node {
+                discriminator: Node::DISCRIMINATOR,
+                version: NODE_VERSION,
+                prev: Pubkey::new_unique(),
+                next: Pubkey::new_unique(),
+                seq: u64::MAX,
+                bump: u8::MAX,
+                data: vec![0xff; data_len],
+            };
+            assert_eq!(borsh::to_vec(&node).unwrap().len(), Node::space(data_len));
+        }
+        assert_eq!(Node::space(0), Node::LEN);
+    }
+
+    #[test]
+    fn test_node_prefix_layout() {
+        let mut node = Node {
+            discriminator: Node::DISCRIMINATOR,
+            version: NODE_VERSION,
+            prev: Pubkey::new_unique(),
+            next: Pubkey::new_unique(),
+            seq: 0x0102_0304_0506_0708,
+            bump: 254,
+            data: vec![1, 2, 3],
+        };
+        let mut bytes = borsh::to_vec(&node).unwrap();
+
+        // The prefix is exactly the Borsh encoding up to the payload
+        let prefix: &mut NodePrefix = bytemuck::from_bytes_mut(&mut bytes[..NodePrefix::LEN]);
+        assert_eq!(prefix.discriminator, Node::DISCRIMINATOR);
+        assert_eq!(prefix.version, NODE_VERSION);
+        assert_eq!(prefix.prev, node.prev);
+        assert_eq!(prefix.next, node.next);
+        assert_eq!(prefix.seq(), node.seq);
+        assert_eq!(prefix.bump, node.bump);
+
+        // Relinking in place matches a full Borsh round-trip
+        let new_next = Pubkey::new_unique();
+        prefix.next = new_next;
+        node.next = new_next;
+        assert_eq!(bytes, borsh::to_vec(&node).unwrap());
+    }
+
+    /// Logs of removing a 1-byte node whose neighbors carry `neighbor_len`
+    /// bytes each, on the SBF build.
+    #[cfg(feature = "bench-relink")]
+    async fn remove_node_logs(neighbor_len: usize) -> Vec<String> {
+        let program_id = Pubkey::new_unique();
+        let mut program_test = ProgramTest::new("circular_doubly_ll_solana", program_id, None);
+        program_test.prefer_bpf(true);
+
+        let list_header_account = Keypair::new();
+        let nodes: Vec<Pubkey> = (0..3)
+            .map(|seq| find_node_address(&program_id, &list_header_account.pubkey(), seq).0)
+            .collect();
+
+        let (mut banks_client, payer, last_blockhash) = program_test.start().await;
+
+        let initialize_instruction = instruction::initialize_list(
+            &program_id,
+            &payer.pubkey(),
+            &list_header_account.pubkey(),
+            neighbor_len as u32,
+        );
+        banks_client
+            .process_transaction(Transaction::new_signed_with_payer(
+                &[initialize_instruction],
+                Some(&payer.pubkey()),
+                &[&payer, &list_header_account],
+                last_blockhash,
+            ))
+            .await
+            .unwrap();
+
+        for (i, data) in [vec![7; neighbor_len], vec![7], vec![7; neighbor_len]]
+            .into_iter()
+            .enumerate()
+        {
+            let neighbors = if i > 0 {
+                vec![nodes[i - 1], nodes[0]]
+            } else {
+                vec![]
+            };
+            let instruction = instruction::add_node(
+                &program_id,
+                &payer.pubkey(),
+                &payer.pubkey(),
+                &list_header_account.pubkey(),
+                &nodes[i],
+                &neighbors,
+                data,
+            );
+            banks_client
+                .process_transaction(Transaction::new_signed_with_payer(
+                    &[instruction],
+                    Some(&payer.pubkey()),
+                    &[&payer],
+                    last_blockhash,
+                ))
+                .await
+                .unwrap();
+        }
+
+        let remove_instruction = instruction::remove_node(
+            &program_id,
+            &payer.pubkey(),
+            &list_header_account.pubkey(),
+            &nodes[1],
+            &payer.pubkey(),
+            &[nodes[0], nodes[2]],
+        );
+        let simulation = banks_client
+            .simulate_transaction(Transaction::new_signed_with_payer(
+                &[remove_instruction],
+                Some(&payer.pubkey()),
+                &[&payer],
+                last_blockhash,
+            ))
+            .await
+            .unwrap();
+        simulation.result.unwrap().unwrap();
+        simulation.simulation_details.unwrap().logs
+    }
+
+    /// Compares the compute units of the two ways of relinking a neighbor:
+    /// the deserialize and re-serialize of the whole node that relinking used
+    /// to do, and the in-place write through `NodePrefix`. The program measures
+    /// both itself when built with the `bench-relink` feature:
+    /// `cargo test-sbf --features bench-relink -- --nocapture bench_relink`.
+    #[cfg(feature = "bench-relink")]
+    #[tokio::test]
+    async fn bench_relink_compute_units() {
+        if !sbf_program_built() {
+            println!("Skipping bench_relink_compute_units: needs the SBF build of the program");
+            return;
+        }
+        for neighbor_len in [1, 512] {
+            let logs = remove_node_logs(neighbor_len).await;
+            let relink = logs
+                .iter()
+                .find_map(|log| log.strip_prefix("Program log: Relink: "))
+                .expect("the SBF build has the bench-relink feature");
+            let units: Vec<u64> = relink
+                .split(' ')
+                .filter_map(|word| word.parse().ok())
+                .collect();
+            let [full, in_place] = units[..] else {
+                panic!("unexpected relink log {relink:?}");
+            };
+            println!(
+                "Relinking a {neighbor_len}-byte neighbor: {full} CU deserializing, {in_place} CU in place"
+            );
+            assert!(in_place < full);
+        }
+    }
+
+    #[test]
+    fn test_event_log_data() {
+        let list_header = Pubkey::new_unique();
+        let events = [
+            Event::ListInitialized {
+                list_header,
+                authority: Pubkey::new_unique(),
+                max_data_len: MAX_DATA_LEN,
+            },
+            Event::NodeAdded {
+                list_header,
+                node: Pubkey::new_unique(),
+                seq: 1,
+                prev: Pubkey::new_unique(),
+                next: Pubkey::new_unique(),
+                data: vec![1, 2],
+            },
+            Event::NodeRemoved {
+                list_header,
+                node: Pubkey::new_unique(),
+                seq: 2,
+            },
+            Event::NodeUpdated {
+                list_header,
+                node: Pubkey::new_unique(),
+                seq: 3,
+                data: vec![],
+            },
+            Event::AuthorityChanged {
+                list_header,
+                new_authority: None,
+            },
+        ];
+        for event in events {
+            let data = event.to_log_data().unwrap();
+            assert_eq!(data[..8], Event::DISCRIMINATOR);
+            assert_eq!(Event::try_from_log_data(&data), Some(event));
+
+            // Data logged by anyone else
+            assert_eq!(Event::try_from_log_data(&data[8..]), None);
+            assert_eq!(Event::try_from_log_data(&data[..data.len() - 1]), None);
+        }
+        assert_eq!(Event::try_from_log_data(b"cdllevnt"), None);
+    }
+
+    /// Checks the events in the `Program data:` lines the SBF build logs;
+    /// natively, `sol_log_data` only prints to stdout.
+    #[tokio::test]
+    async fn test_events() {
+        if !sbf_program_built() {
+            println!("Skipping test_events: needs the SBF build of the program");
+            return;
+        }
+        let program_id = Pubkey::new_unique();
+        let mut program_test = ProgramTest::new("circular_doubly_ll_solana", program_id, None);
+        program_test.prefer_bpf(true);
+
+        let list_header_account = Keypair::new();
+        let list = list_header_account.pubkey();
+        let (node0, _) = find_node_address(&program_id, &list, 0);
+        let (node1, _) = find_node_address(&program_id, &list, 1);
+        let new_authority = Pubkey::new_unique();
+
+        let ProgramTestContext {
+            mut banks_client,
+            last_blockhash,
+            payer,
+            ..
+        } = program_test.start_with_context().await;
+
+        let authority = payer.pubkey();
+        let add_node = |new_node: Pubkey, data: Vec<u8>, neighbors: &[Pubkey]| {
+            instruction::add_node(
+                &program_id,
+                &authority,
+                &authority,
+                &list,
+                &new_node,
+                neighbors,
+                data,
+            )
+        };
+        let transaction = Transaction::new_signed_with_payer(
+            &[
+                initialize_list_instruction(program_id, &payer, &list_header_account),
+                add_node(node0, vec![1, 2], &[]),
+                add_node(node1, vec![3], &[node0]),
+                instruction::update_node(
+                    &program_id,
+                    &authority,
+                    &authority,
+                    &list,
+                    &node1,
+                    vec![4],
+                ),
+                instruction::pop_front(
+                    &program_id,
+                    &authority,
+                    &list,
+                    &node0,
+                    &authority,
+                    &[node1],
+                ),
+                instruction::set_authority(&program_id, &authority, &list, Some(&new_authority)),
+            ],
+            Some(&payer.pubkey()),
+            &[&payer, &list_header_account],
+            last_blockhash,
+        );
+        let result = banks_client
+            .process_transaction_with_metadata(transaction)
+            .await
+            .unwrap();
+        assert!(result.result.is_ok());
+        let logs = result.metadata.unwrap().log_messages;
+
+        // The debug output is gone
+        assert!(!logs.iter().any(|log| log.contains("AccountInfo")));
+        assert!(!logs.iter().any(|log| log == "Program log: 1"));
+        assert!(!logs.iter().any(|log| log.contains(&node1.to_string())));
+
+        let events = decode_events(&program_id, &logs);
+        assert_eq!(
+            events,
+            vec![
+                Event::ListInitialized {
+                    list_header: list,
+                    authority: payer.pubkey(),
+                    max_data_len: MAX_DATA_LEN,
+                },
+                Event::NodeAdded {
+                    list_header: list,
+                    node: node0,
+                    seq: 0,
+                    prev: node0,
+                    next: node0,
+                    data: vec![1, 2],
+                },
+                Event::NodeAdded {
+                    list_header: list,
+                    node: node1,
+                    seq: 1,
+                    prev: node0,
+                    next: node0,
+                    data: vec![3],
+                },
+                Event::NodeUpdated {
+                    list_header: list,
+                    node: node1,
+                    seq: 1,
+                    data: vec![4],
+                },
+                Event::NodeRemoved {
+                    list_header: list,
+                    node: node0,
+                    seq: 0,
+                },
+                Event::AuthorityChanged {
+                    list_header: list,
+                    new_authority: Some(new_authority),
+                },
+            ]
+        );
+    }
+
+    /// `decode_events` only trusts data lines logged while the given program is
+    /// the innermost one running.
+    #[test]
+    fn test_decode_events() {
+        let program_id = Pubkey::new_unique();
+        let other_program = Pubkey::new_unique();
+        let list_header = Pubkey::new_unique();
+        let event = |node: Pubkey| Event::NodeRemoved {
+            list_header,
+            node,
+            seq: 0,
+        };
+        let data_log = |event: &Event| {
+            format!(
+                "{}{}",
+                PROGRAM_DATA_LOG_PREFIX,
+                STANDARD.encode(event.to_log_data().unwrap())
+            )
+        };
+        let invoke = |program: &Pubkey, depth: u8| format!("Program {program} invoke [{depth}]");
+        let success = |program: &Pubkey| format!("Program {program} success");
+        let (ours, forged_before, forged_inner, forged_after) = (
+            event(Pubkey::new_unique()),
+            event(Pubkey::new_unique()),
+            event(Pubkey::new_unique()),
+            event(Pubkey::new_unique()),
+        );
+
+        let mut logs = vec![
+            // Another program logging before ours runs
+            invoke(&other_program, 1),
+            data_log(&forged_before),
+            success(&other_program),
+            invoke(&program_id, 1),
+            "Program log: Instruction: RemoveNode".to_string(),
+            // A program invoked by ours
+            invoke(&other_program, 2),
+            data_log(&forged_inner),
+            // A program cannot fake runtime lines with its own logs
+            format!("Program log: Program {program_id} invoke [3]"),
+            data_log(&forged_inner),
+            success(&other_program),
+            data_log(&ours),
+            format!("Program {program_id} consumed 5000 of 200000 compute units"),
+            success(&program_id),
+            invoke(&other_program, 1),
+            data_log(&forged_after),
+            success(&other_program),
+        ];
+        assert_eq!(decode_events(&program_id, &logs), vec![ours.clone()]);
+        assert_eq!(
+            decode_events(&other_program, &logs),
+            vec![
+                forged_before,
+                forged_inner.clone(),
+                forged_inner,
+                forged_after
+            ]
+        );
+
+        // Nothing happened in a failed transaction
+        logs.push(invoke(&program_id, 1));
+        logs.push(format!(
+            "Program {program_id} failed: custom program error: 0x0"
+        ));
+        assert!(decode_events(&program_id, &logs).is_empty());
+
+        // Lines that are not events are skipped
+        let logs = [
+            invoke(&program_id, 1),
+            "Program data: AAAA".to_string(),
+            "Program log: Instruction: AddNode".to_string(),
+            success(&program_id),
+        ];
+        assert!(decode_events(&program_id, &logs).is_empty());
+    }
+
+    /// Whether program-test can load the SBF build of the program, as under
+    /// `cargo test-sbf`. Tests that meter compute units are skipped otherwise.
+    fn sbf_program_built() -> bool {
+        find_file("circular_doubly_ll_solana.so").is_some()
+    }
+
+    /// Upper bounds for the compute units of each instruction with the
+    /// largest payloads. These are estimates that have not been measured
+    /// yet: replace each with what `test_compute_units` prints under
+    /// `cargo test-sbf` plus about 10%, and re-record them when an
+    /// instruction changes.
+    const COMPUTE_BUDGETS: &[(&str, u64)] = &[
+        ("InitializeList", 10_000),
+        ("AddNode", 25_000),
+        ("PushFront", 25_000),
+        ("InsertAfter", 30_000),
+        ("UpdateNode", 15_000),
+        ("VerifyList", 20_000),
+        ("RemoveNode", 15_000),
+        ("PopFront", 15_000),
+        ("PopBack", 15_000),
+        ("ClearList", 10_000),
+        ("SetAuthority", 5_000),
+    ];
+
+    /// Simulates the instruction, checks its compute units against its
+    /// budget, then processes it.
+    async fn process_metered(
+        banks_client: &mut BanksClient,
+        name: &str,
+        instruction: Instruction,
+        signers: &[&Keypair],
+        last_blockhash: Hash,
+    ) {
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction],
+            Some(&signers[0].pubkey()),
+            signers,
+            last_blockhash,
+        );
+        let simulation = banks_client
+            .simulate_transaction(transaction.clone())
+            .await
+            .unwrap();
+        simulation.result.unwrap().unwrap();
+        let units = simulation.simulation_details.unwrap().units_consumed;
+
+        let (_, budget) = COMPUTE_BUDGETS
+            .iter()
+            .find(|(budget_name, _)| *budget_name == name)
+            .unwrap();
+        println!("{name}: {units} CU, budget {budget}");
+        assert!(
+            units <= *budget,
+            "{name} used {units} compute units, its budget is {budget}"
+        );
+
+        banks_client.process_transaction(transaction).await.unwrap();
+    }
+
+    /// Only the SBF build meters the program itself; run natively, every
+    /// instruction would stay far below its budget whatever it costs, so a
+    /// plain `cargo test` skips this.
+    #[tokio::test]
+    async fn test_compute_units() {
+        if !sbf_program_built() {
+            println!("Skipping test_compute_units: needs the SBF build of the program");
+            return;
+        }
+        let program_id = Pubkey::new_unique();
+        let mut program_test = ProgramTest::new("circular_doubly_ll_solana", program_id, None);
+        program_test.prefer_bpf(true);
+
+        let list_header_account = Keypair::new();
+        let list = list_header_account.pubkey();
+        let node: Vec<Pubkey> = (0..4)
+            .map(|seq| find_node_address(&program_id, &list, seq).0)
+            .collect();
+        // Budgets hold for the largest payloads the list accepts
+        let data = vec![7; MAX_DATA_LEN as usize];
+
+        let (mut banks_client, payer, last_blockhash) = program_test.start().await;
+        let authority = payer.pubkey();
+
+        process_metered(
+            &mut banks_client,
+            "InitializeList",
+            instruction::initialize_list(&program_id, &authority, &list, MAX_DATA_LEN),
+            &[&payer, &list_header_account],
+            last_blockhash,
+        )
+        .await;
+
+        // node0
+        process_metered(
+            &mut banks_client,
+            "AddNode",
+            instruction::add_node(
+                &program_id,
+                &authority,
+                &authority,
+                &list,
+                &node[0],
+                &[],
+                data.clone(),
+            ),
+            &[&payer],
+            last_blockhash,
+        )
+        .await;
+        // node0 node1
+        process_metered(
+            &mut banks_client,
+            "AddNode",
+            instruction::add_node(
+                &program_id,
+                &authority,
+                &authority,
+                &list,
+                &node[1],
+                &[node[0]],
+                data.clone(),
+            ),
+            &[&payer],
+            last_blockhash,
+        )
+        .await;
+        // node2 node0 node1
+        process_metered(
+            &mut banks_client,
+            "PushFront",
+            instruction::push_front(
+                &program_id,
+                &authority,
+                &authority,
+                &list,
+                &node[2],
+                &[node[1], node[0]],
+                data.clone(),
+            ),
+            &[&payer],
+            last_blockhash,
+        )
+        .await;
+        // node2 node0 node3 node1
+        process_metered(
+            &mut banks_client,
+            "InsertAfter",
+            instruction::insert_after(
+                &program_id,
+                &authority,
+                &authority,
+                &list,
+                &node[3],
+                &node[0],
+                &node[1],
+                data.clone(),
+            ),
+            &[&payer],
+            last_blockhash,
+        )
+        .await;
+
+        process_metered(
+            &mut banks_client,
+            "UpdateNode",
+            instruction::update_node(
+                &program_id,
+                &authority,
+                &authority,
+                &list,
+                &node[3],
+                vec![8; MAX_DATA_LEN as usize],
+            ),
+            &[&payer],
+            last_blockhash,
+        )
+        .await;
+
+        process_metered(
+            &mut banks_client,
+            "VerifyList",
+            instruction::verify_list(&program_id, &list, &[node[2], node[0], node[3], node[1]]),
+            &[&payer],
+            last_blockhash,
+        )
+        .await;
+
+        // node2 node0 node1
+        process_metered(
+            &mut banks_client,
+            "RemoveNode",
+            instruction::remove_node(
+                &program_id,
+                &authority,
+                &list,
+                &node[3],
+                &authority,
+                &[node[0], node[1]],
+            ),
+            &[&payer],
+            last_blockhash,
+        )
+        .await;
+        // node0 node1
+        process_metered(
+            &mut banks_client,
+            "PopFront",
+            instruction::pop_front(
+                &program_id,
+                &authority,
+                &list,
+                &node[2],
+                &authority,
+                &[node[1], node[0]],
+            ),
+            &[&payer],
+            last_blockhash,
+        )
+        .await;
+        // node0
+        process_metered(
+            &mut banks_client,
+            "PopBack",
+            instruction::pop_back(
+                &program_id,
+                &authority,
+                &list,
+                &node[1],
+                &authority,
+                &[node[0]],
+            ),
+            &[&payer],
+            last_blockhash,
+        )
+        .await;
+
+        process_metered(
+            &mut banks_client,
+            "ClearList",
+            instruction::clear_list(&program_id, &authority, &list, &authority, &[node[0]], &[]),
+            &[&payer],
+            last_blockhash,
+        )
+        .await;
+
+        process_metered(
+            &mut banks_client,
+            "SetAuthority",
+            instruction::set_authority(&program_id, &authority, &list, None),
+            &[&payer],
+            last_blockhash,
+        )
+        .await;
+
+        assert!(get_list_header(&mut banks_client, list).await.is_empty());
+    }
+
+    /// Runs random operations through the program and through
+    /// `model::CircularDoublyLinkedList`, comparing the outcome and the whole
+    /// list after every step.
+    #[tokio::test]
+    async fn test_model_matches_program() {
+        use crate::model::CircularDoublyLinkedList;
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+        use solana_sdk::compute_budget::ComputeBudgetInstruction;
+
+        const STEPS: u32 = 200;
+
+        for seed in 0..2 {
+            let program_id = Pubkey::new_unique();
+            let program_test = ProgramTest::new(
+                "circular_doubly_ll_solana",
+                program_id,
+                processor!(process_instruction),
+            );
+            let (mut banks_client, payer, last_blockhash) = program_test.start().await;
+            let mut rng = StdRng::seed_from_u64(seed);
+
+            let list_header = Keypair::new();
+            let list = list_header.pubkey();
+            let transaction = Transaction::new_signed_with_payer(
+                &[initialize_list_instruction(
+                    program_id,
+                    &payer,
+                    &list_header,
+                )],
+                Some(&payer.pubkey()),
+                &[&payer, &list_header],
+                last_blockhash,
+            );
+            banks_client.process_transaction(transaction).await.unwrap();
+
+            let mut model = CircularDoublyLinkedList::new(payer.pubkey(), MAX_DATA_LEN);
+            let node = |seq: u64| find_node_address(&program_id, &list, seq).0;
+            // The authority is handed back and forth between these
+            let keypairs = [payer.insecure_clone(), Keypair::new()];
+
+            for step in 0..STEPS {
+                // Mostly signed by the current authority
+                let current = keypairs
+                    .iter()
+                    .position(|keypair| Some(&keypair.pubkey()) == model.authority())
+                    .unwrap_or(0);
+                let signer = if rng.gen_ratio(1, 10) {
+                    &keypairs[1 - current]
+                } else {
+                    &keypairs[current]
+                };
+                let authority = signer.pubkey();
+                let other = keypairs[1 - current].pubkey();
+
+                // Occasionally one byte too many
+                let data_len = rng.gen_range(0..=MAX_DATA_LEN as usize + 1);
+                let data: Vec<u8> = (0..data_len).map(|_| rng.gen()).collect();
+                let seqs: Vec<u64> = model.iter().map(|(seq, _)| seq).collect();
+                let target = (!seqs.is_empty()).then(|| seqs[rng.gen_range(0..seqs.len())]);
+                let neighbors = |seq: u64| -> Vec<Pubkey> {
+                    match model.links(seq) {
+                        Some((prev, next)) if prev != seq => vec![node(prev), node(next)],
+                        _ => vec![],
+                    }
+                };
+                let ends: Vec<Pubkey> = model
+                    .tail()
+                    .zip(model.head())
+                    .map(|(tail, head)| vec![node(tail), node(head)])
+                    .unwrap_or_default();
+                let new_node = node(model.next_seq());
+                // Stands in for the head or tail of an empty list
+                let head = model.head().map_or(new_node, node);
+                let tail = model.tail().map_or(new_node, node);
+
+                let (name, instruction, expected) = match (rng.gen_range(0..100), target) {
+                    (20..=29, _) => (
+                        "PushFront",
+                        instruction::push_front(
+                            &program_id,
+                            &authority,
+                            &payer.pubkey(),
+                            &list,
+                            &new_node,
+                            &ends,
+                            data.clone(),
+                        ),
+                        model.push_front(&authority, data).map(|_| None),
+                    ),
+                    (30..=39, Some(anchor)) => (
+                        "InsertAfter",
+                        instruction::insert_after(
+                            &program_id,
+                            &authority,
+                            &payer.pubkey(),
+                            &list,
+                            &new_node,
+                            &node(anchor),
+                            &node(model.links(anchor).unwrap().1),
+                            data.clone(),
+                        ),
+                        model.insert_after(&authority, anchor, data).map(|_| None),
+                    ),
+                    (40..=49, Some(anchor)) => (
+                        "InsertBefore",
+                        instruction::insert_before(
+                            &program_id,
+                            &authority,
+                            &payer.pubkey(),
+                            &list,
+                            &new_node,
+                            &node(anchor),
+                            &node(model.links(anchor).unwrap().0),
+                            data.clone(),
+                        ),
+                        model.insert_before(&authority, anchor, data).map(|_| None),
+                    ),
+                    (50..=59, Some(target)) => (
+                        "RemoveNode",
+                        instruction::remove_node(
+                            &program_id,
+                            &authority,
+                            &list,
+                            &node(target),
+                            &payer.pubkey(),
+                            &neighbors(target),
+                        ),
+                        model.remove(&authority, target).map(|_| None),
+                    ),
+                    (60..=66, _) => (
+                        "PopFront",
+                        instruction::pop_front(
+                            &program_id,
+                            &authority,
+                            &list,
+                            &head,
+                            &payer.pubkey(),
+                            &model.head().map(neighbors).unwrap_or_default(),
+                        ),
+                        model.pop_front(&authority).map(Some),
+                    ),
+                    (67..=73, _) => (
+                        "PopBack",
+                        instruction::pop_back(
+                            &program_id,
+                            &authority,
+                            &list,
+                            &tail,
+                            &payer.pubkey(),
+                            &model.tail().map(neighbors).unwrap_or_default(),
+                        ),
+                        model.pop_back(&authority).map(Some),
+                    ),
+                    (74..=81, Some(target)) => (
+                        "UpdateNode",
+                        instruction::update_node(
+                            &program_id,
+                            &authority,
+                            &payer.pubkey(),
+                            &list,
+                            &node(target),
+                            data.clone(),
+                        ),
+                        model.update(&authority, target, data).map(|_| None),
+                    ),
+                    (82..=85, Some(target)) => {
+                        let expected = if rng.gen() {
+                            model.get(target).unwrap().clone()
+                        } else {
+                            vec![rng.gen()]
+                        };
+                        (
+                            "UpdateNodeIfEquals",
+                            instruction::update_node_if_equals(
+                                &program_id,
+                                &authority,
+                                &payer.pubkey(),
+                                &list,
+                                &node(target),
+                                expected.clone(),
+                                data.clone(),
+                            ),
+                            model
+                                .update_if_equals(&authority, target, &expected, data)
+                                .map(|_| None),
+                        )
+                    }
+                    (86..=91, _) => {
+                        let max_nodes = rng.gen_range(0..=seqs.len().min(4));
+                        let nodes: Vec<Pubkey> =
+                            seqs[..max_nodes].iter().copied().map(node).collect();
+                        let remaining = match seqs.get(max_nodes) {
+                            Some(&new_head) => vec![node(new_head), tail],
+                            None => vec![],
+                        };
+                        (
+                            "ClearList",
+                            instruction::clear_list(
+                                &program_id,
+                                &authority,
+                                &list,
+                                &payer.pubkey(),
+                                &nodes,
+                                &remaining,
+                            ),
+                            model.clear(&authority, max_nodes as u32).map(|_| None),
+                        )
+                    }
+                    (92..=95, _) => {
+                        let nodes: Vec<Pubkey> = seqs.iter().copied().map(node).collect();
+                        (
+                            "VerifyList",
+                            instruction::verify_list(&program_id, &list, &nodes),
+                            model.verify().map(|_| None),
+                        )
+                    }
+                    (96..=98, _) => (
+                        "SetAuthority",
+                        instruction::set_authority(&program_id, &authority, &list, Some(&other)),
+                        model.set_authority(&authority, Some(other)).map(|_| None),
+                    ),
+                    // Renouncing makes every later step fail, so not too early
+                    (99, _) if step >= STEPS * 3 / 4 => (
+                        "SetAuthority(None)",
+                        instruction::set_authority(&program_id, &authority, &list, None),
+                        model.set_authority(&authority, None).map(|_| None),
+                    ),
+                    // Also whatever needs a node while the list is empty
+                    _ => (
+                        "PushBack",
+                        instruction::push_back(
+                            &program_id,
+                            &authority,
+                            &payer.pubkey(),
+                            &list,
+                            &new_node,
+                            &ends,
+                            data.clone(),
+                        ),
+                        model.push_back(&authority, data).map(|_| None),
+                    ),
+                };
+                let context = format!("seed {seed}, step {step}: {name}");
+
+                let mut signers = vec![&payer];
+                if signer.pubkey() != payer.pubkey()
+                    && instruction
+                        .accounts
+                        .iter()
+                        .any(|meta| meta.is_signer && meta.pubkey == signer.pubkey())
+                {
+                    signers.push(signer);
+                }
+                // The changing compute unit limit keeps identical operations
+                // from being deduplicated
+                let blockhash = banks_client.get_latest_blockhash().await.unwrap();
+                let transaction = Transaction::new_signed_with_payer(
+                    &[
+                        ComputeBudgetInstruction::set_compute_unit_limit(1_000_000 + step),
+                        instruction,
+                    ],
+                    Some(&payer.pubkey()),
+                    &signers,
+                    blockhash,
+                );
+                let result = banks_client
+                    .process_transaction_with_metadata(transaction)
+                    .await
+                    .unwrap();
+                let outcome = match result.result {
+                    Ok(()) => Ok(()),
+                    Err(TransactionError::InstructionError(1, InstructionError::Custom(code))) => {
+                        Err(code)
+                    }
+                    Err(e) => panic!("{context}: {e}"),
+                };
+                assert_eq!(
+                    outcome,
+                    expected.as_ref().map(|_| ()).map_err(|e| *e as u32),
+                    "{context}"
+                );
+                if let Ok(Some(popped)) = expected {
+                    let return_data = result.metadata.unwrap().return_data;
+                    assert_eq!(
+                        return_data.map(|r| r.data).unwrap_or_default(),
+                        popped,
+                        "{context}"
+                    );
+                }
+
+                let header = get_list_header(&mut banks_client, list).await;
+                assert_eq!(header.len, model.len() as u64, "{context}");
+                assert_eq!(header.next_seq, model.next_seq(), "{context}");
+                assert_eq!(
+                    header.authority,
+                    model.authority().copied().unwrap_or_default(),
+                    "{context}"
+                );
+                let expected_nodes: Vec<(Pubkey, Vec<u8>)> = model
+                    .iter()
+                    .map(|(seq, data)| (node(seq), data.clone()))
+                    .collect();
+                assert_eq!(
+                    walk_list(&mut banks_client, list).await,
+                    expected_nodes,
+                    "{context}"
+                );
+            }
+        }
+    }
+
+    #[cfg(feature = "client")]
+    #[tokio::test]
+    async fn test_list_client() {
+        use crate::client::{ClientError, ListClient};
+
+        let program_id = Pubkey::new_unique();
+        let program_test = ProgramTest::new(
+            "circular_doubly_ll_solana",
+            program_id,
+            processor!(process_instruction),
+        );
+
+        let (banks_client, payer, _) = program_test.start().await;
+        let authority = payer.insecure_clone();
+        let mut client = ListClient::new(banks_client, program_id, payer);
+
+        let list_header_account = Keypair::new();
+        let list = list_header_account.pubkey();
+        client
+            .initialize_list(&list_header_account, MAX_DATA_LEN)
+            .await
+            .unwrap();
+        assert!(client.fetch_list(&list).await.unwrap().is_empty());
+        assert!(client.iter_nodes(&list).await.unwrap().is_empty());
+
+        let mut nodes = vec![];
+        for data in 1..=3 {
+            nodes.push(client.append(&list, &authority, vec![data]).await.unwrap());
+        }
+        let contents = |nodes: Vec<(Pubkey, Node)>| -> Vec<(Pubkey, Vec<u8>)> {
+            nodes
+                .into_iter()
+                .map(|(key, node)| (key, node.data))
+                .collect()
+        };
+        assert_eq!(
+            contents(client.iter_nodes(&list).await.unwrap()),
+            vec![
+                (nodes[0], vec![1]),
+                (nodes[1], vec![2]),
+                (nodes[2], vec![3])
+            ]
+        );
+
+        // Neighbors are looked up by the client
+        let rent_receiver = Pubkey::new_unique();
+        client
+            .remove(&list, &authority, &nodes[1], &rent_receiver)
+            .await
+            .unwrap();
+        assert_eq!(
+            contents(client.iter_nodes(&list).await.unwrap()),
+            vec![(nodes[0], vec![1]), (nodes[2], vec![3])]
+        );
+        for node in [nodes[0], nodes[2]] {
+            client
+                .remove(&list, &authority, &node, &rent_receiver)
+                .await
+                .unwrap();
+        }
+        assert!(client.fetch_list(&list).await.unwrap().is_empty());
+
+        assert!(matches!(
+            client.fetch_node(&nodes[1]).await,
+            Err(ClientError::AccountNotFound(key)) if key == nodes[1]
+        ));
+        assert!(matches!(
+            client.fetch_node(&list).await,
+            Err(ClientError::InvalidAccount(key, "node")) if key == list
+        ));
+    }
+
+    #[cfg(feature = "client")]
+    #[tokio::test]
+    async fn test_list_iter() {
+        use crate::client::{ClientError, ListClient, ListIter, Rpc};
+        use solana_sdk::{account::Account, transaction::Transaction};
+        use std::collections::HashSet;
+
+        /// Counts the batched fetches made through it and the addresses they
+        /// asked for.
+        struct CountingRpc {
+            banks_client: BanksClient,
+            batches: usize,
+            requested: Vec<Pubkey>,
+        }
+
+        #[async_trait::async_trait]
+        impl Rpc for CountingRpc {
+            async fn get_account(
+                &mut self,
+                address: &Pubkey,
+            ) -> Result<Option<Account>, ClientError> {
+                Rpc::get_account(&mut self.banks_client, address).await
+            }
+
+            async fn get_multiple_accounts(
+                &mut self,
+                addresses: &[Pubkey],
+            ) -> Result<Vec<Option<Account>>, ClientError> {
+                self.batches += 1;
+                self.requested.extend_from_slice(addresses);
+                self.banks_client.get_multiple_accounts(addresses).await
+            }
+
+            async fn get_latest_blockhash(&mut self) -> Result<Hash, ClientError> {
+                Rpc::get_latest_blockhash(&mut self.banks_client).await
+            }
+
+            async fn send_transaction(
+                &mut self,
+                transaction: Transaction,
+            ) -> Result<(), ClientError> {
+                Rpc::send_transaction(&mut self.banks_client, transaction).await
+            }
+        }
+
+        let program_id = Pubkey::new_unique();
+        let mut program_test = ProgramTest::new(
+            "circular_doubly_ll_solana",
+            program_id,
+            processor!(process_instruction),
+        );
+        let authority = Pubkey::new_unique();
+        let ring = |len: usize| {
+            move |i: usize, keys: &[Pubkey]| (keys[(i + len - 1) % len], keys[(i + 1) % len])
+        };
+        let (list, nodes) = add_list_accounts(&mut program_test, program_id, authority, 5, ring(5));
+        // The tail links back to the second node instead of the head
+        let (cycle_list, cycle_nodes) =
+            add_list_accounts(&mut program_test, program_id, authority, 4, |i, keys| {
+                let (prev, next) = ring(4)(i, keys);
+                (prev, if i == 3 { keys[1] } else { next })
+            });
+        let missing = Pubkey::new_unique();
+        let (dangling_list, dangling_nodes) =
+            add_list_accounts(&mut program_test, program_id, authority, 3, |i, keys| {
+                let (prev, next) = ring(3)(i, keys);
+                match i {
+                    1 => (prev, missing),
+                    2 => (list, next),
+                    _ => (prev, next),
+                }
+            });
+        // The header counts three nodes but the second one closes the ring
+        let (short_list, _) =
+            add_list_accounts(&mut program_test, program_id, authority, 3, |i, keys| {
+                (ring(3)(i, keys).0, keys[(i + 1) % 2])
+            });
+
+        // The last node links back past the second one
+        let (back_link_list, back_link_nodes) =
+            add_list_accounts(&mut program_test, program_id, authority, 3, |i, keys| {
+                let (prev, next) = ring(3)(i, keys);
+                (if i == 2 { keys[0] } else { prev }, next)
+            });
+
+        let (banks_client, payer, _) = program_test.start().await;
+        let rpc = CountingRpc {
+            banks_client,
+            batches: 0,
+            requested: vec![],
+        };
+        let mut client = ListClient::new(rpc, program_id, payer);
+
+        let keys = |nodes: &[(Pubkey, Node)]| -> Vec<Pubkey> {
+            nodes.iter().map(|(key, _)| *key).collect()
+        };
+
+        // Nodes with consecutive sequence numbers come in one batch
+        let forward = ListIter::forward(&mut client, &list)
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(keys(&forward), nodes);
+        assert_eq!(client.rpc().batches, 2);
+        let reverse = ListIter::reverse(&mut client, &list)
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(
+            keys(&reverse),
+            nodes.iter().rev().copied().collect::<Vec<_>>()
+        );
+        assert_eq!(client.rpc().batches, 4);
+
+        let mut iter = ListIter::forward(&mut client, &cycle_list);
+        for key in &cycle_nodes {
+            assert_eq!(iter.try_next().await.unwrap().unwrap().0, *key);
+        }
+        assert!(matches!(
+            iter.try_next().await,
+            Err(ClientError::Cycle { node, position: 4 }) if node == cycle_nodes[1]
+        ));
+        assert!(iter.try_next().await.unwrap().is_none());
+
+        let mut iter = ListIter::forward(&mut client, &dangling_list);
+        iter.try_next().await.unwrap();
+        iter.try_next().await.unwrap();
+        assert!(matches!(
+            iter.try_next().await,
+            Err(ClientError::DanglingPointer { from, to })
+                if from == dangling_nodes[1] && to == missing
+        ));
+        // A list header is not a node either
+        let mut iter = ListIter::reverse(&mut client, &dangling_list);
+        assert_eq!(iter.try_next().await.unwrap().unwrap().0, dangling_nodes[2]);
+        assert!(matches!(
+            iter.try_next().await,
+            Err(ClientError::DanglingPointer { from, to })
+                if from == dangling_nodes[2] && to == list
+        ));
+
+        assert!(matches!(
+            client.iter_nodes(&short_list).await,
+            Err(ClientError::LengthMismatch {
+                expected: 3,
+                found: 2
+            })
+        ));
+
+        // Verifying also follows the back links, which walking forward does not
+        assert_eq!(client.verify_list(&list).await.unwrap(), 5);
+        assert_eq!(
+            keys(&client.iter_nodes(&back_link_list).await.unwrap()),
+            back_link_nodes
+        );
+        assert!(matches!(
+            client.verify_list(&back_link_list).await,
+            Err(ClientError::BrokenLink { node, expected, found })
+                if node == back_link_nodes[2]
+                    && expected == back_link_nodes[1]
+                    && found == back_link_nodes[0]
+        ));
+        assert!(matches!(
+            client.verify_list(&short_list).await,
+            Err(ClientError::LengthMismatch { .. })
+        ));
+
+        // [6, 2, 1, 0] with 3, 4 and 5 closed: walking forward, both the
+        // batch for 1 and the one for 0 would guess at 3, 4 and 5
+        let sparse_list = Keypair::new();
+        let owner = client.payer().insecure_clone();
+        client
+            .initialize_list(&sparse_list, MAX_DATA_LEN)
+            .await
+            .unwrap();
+        let sparse_list = sparse_list.pubkey();
+        let sparse_node = |seq: u64| find_node_address(&program_id, &sparse_list, seq).0;
+        for seq in 0..7 {
+            let header = client.fetch_list(&sparse_list).await.unwrap();
+            if header.is_empty() || (3..6).contains(&seq) {
+                client
+                    .append(&sparse_list, &owner, vec![seq as u8])
+                    .await
+                    .unwrap();
+            } else {
+                let instruction = instruction::push_front(
+                    &program_id,
+                    &owner.pubkey(),
+                    &owner.pubkey(),
+                    &sparse_list,
+                    &sparse_node(seq),
+                    &[header.tail, header.head],
+                    vec![seq as u8],
+                );
+                client.send(&[instruction], &[&owner]).await.unwrap();
+            }
+            if seq == 5 {
+                for closed in 3..6 {
+                    client
+                        .remove(&sparse_list, &owner, &sparse_node(closed), &owner.pubkey())
+                        .await
+                        .unwrap();
+                }
+            }
+        }
+
+        client.rpc().requested.clear();
+        let sparse = client.iter_nodes(&sparse_list).await.unwrap();
+        assert_eq!(keys(&sparse), [6, 2, 1, 0].map(sparse_node).to_vec());
+        // Addresses found empty once are not asked for again, nor are nodes
+        // that were already fetched
+        let requested = &client.rpc().requested;
+        for closed in 3..6 {
+            let address = sparse_node(closed);
+            assert_eq!(requested.iter().filter(|a| **a == address).count(), 1);
+        }
+        let unique: HashSet<&Pubkey> = requested.iter().collect();
+        assert_eq!(unique.len(), requested.len());
+    }
+
+    #[cfg(feature = "idl")]
+    #[test]
+    fn test_idl() {
+        use crate::idl::idl;
+
+        let generated = idl(None);
+        let checked_in: serde_json::Value =
+            serde_json::from_str(include_str!("../idl/circular_doubly_ll_solana.json")).unwrap();
+        assert_eq!(
+            generated, checked_in,
+            "idl/ is stale, regenerate it with `cargo run --features idl --bin cdll-idl`"
+        );
+
+        let instructions = generated["instructions"].as_array().unwrap();
+        for (i, instruction) in instructions.iter().enumerate() {
+            assert_eq!(instruction["discriminant"]["value"], i);
+        }
+        // One past the last variant is not an instruction
+        assert!(InstructionData::try_from_slice(&[instructions.len() as u8]).is_err());
+
+        // Only the neighbors an empty list does not have are optional
+        let optional_accounts = |name: &str| -> Vec<String> {
+            let instruction = instructions
+                .iter()
+                .find(|instruction| instruction["name"] == name)
+                .unwrap();
+            instruction["accounts"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .filter(|account| account["isOptional"] == true)
+                .map(|account| account["name"].as_str().unwrap().to_string())
+                .collect()
+        };
+        for name in ["addNode", "pushFront", "pushBack"] {
+            assert_eq!(optional_accounts(name), ["tail", "head"]);
+        }
+        for name in ["insertAfter", "insertBefore"] {
+            assert!(optional_accounts(name).is_empty());
+        }
+        for name in ["removeNode", "popFront", "popBack"] {
+            assert_eq!(optional_accounts(name), ["prev", "next"]);
+        }
+
+        let errors = generated["errors"].as_array().unwrap();
+        assert_eq!(errors.len(), CdllError::UnsupportedVersion as usize + 1);
+        assert_eq!(errors[17]["name"], "InvalidTargetNode");
+
+        let program_id = Pubkey::new_unique();
+        assert_eq!(
+            idl(Some(&program_id))["metadata"]["address"],
+            program_id.to_string()
+        );
+    }
+
+    #[cfg(feature = "cli")]
+    #[tokio::test]
+    async fn test_cli() {
+        use crate::{
+            cli::{run, Cli},
+            client::ListClient,
+        };
+        use clap::Parser;
+
+        let program_id = Pubkey::new_unique();
+        let program_test = ProgramTest::new(
+            "circular_doubly_ll_solana",
+            program_id,
+            processor!(process_instruction),
+        );
+        let (banks_client, payer, _) = program_test.start().await;
+        let mut client = ListClient::new(banks_client, program_id, payer);
+
+        // Runs `cdll --program-id <program_id> <args>` and returns its output
+        async fn cdll(
+            client: &mut ListClient<BanksClient>,
+            args: &[&str],
+        ) -> Result<String, Box<dyn std::error::Error>> {
+            let program_id = client.program_id().to_string();
+            let mut argv = vec!["cdll", "--program-id", &program_id];
+            argv.extend(args);
+            let cli = Cli::try_parse_from(argv).unwrap();
+            let mut out = vec![];
+            run(client, cli.command, &mut out).await?;
+            Ok(String::from_utf8(out).unwrap())
+        }
+
+        let list = cdll(&mut client, &["init", "--max-data-len", "16"])
+            .await
+            .unwrap();
+        let list = list.trim();
+
+        let b = cdll(&mut client, &["push", list, "b"]).await.unwrap();
+        let b = b.trim();
+        cdll(&mut client, &["push", list, "a", "--front"])
+            .await
+            .unwrap();
+        cdll(&mut client, &["insert-after", list, b, "c"])
+            .await
+            .unwrap();
+        let d = cdll(&mut client, &["push", list, "d"]).await.unwrap();
+
+        let show = cdll(&mut client, &["show", list]).await.unwrap();
+        let lines: Vec<&str> = show.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert!(lines[0].contains(": 4 nodes, authority "));
+        assert!(lines[1].ends_with("\"a\""));
+        assert!(lines[2].starts_with(&format!("1: {b}")));
+        assert!(lines[3].ends_with("\"c\""));
+        assert!(lines[4].starts_with(&format!("3: {}", d.trim())));
+
+        // Verifying does not send a transaction, so it costs nothing
+        let payer = client.payer().pubkey();
+        let balance = client.rpc().get_balance(payer).await.unwrap();
+        assert_eq!(
+            cdll(&mut client, &["verify", list]).await.unwrap(),
+            "List of 4 nodes verified\n"
+        );
+        assert_eq!(client.rpc().get_balance(payer).await.unwrap(), balance);
+
+        assert_eq!(
+            cdll(&mut client, &["pop", list, "--front"]).await.unwrap(),
+            "\"a\"\n"
+        );
+        assert_eq!(cdll(&mut client, &["pop", list]).await.unwrap(), "\"d\"\n");
+        cdll(&mut client, &["remove", list, b]).await.unwrap();
 
-        let head_node = Node::try_from_slice(&head_account_data).unwrap();
+        let show = cdll(&mut client, &["show", list]).await.unwrap();
+        assert_eq!(show.lines().count(), 2);
+        assert!(show.lines().nth(1).unwrap().ends_with("\"c\""));
 
-        assert_eq!(head_node.prev, head_account.pubkey());
-        assert_eq!(head_node.next, head_account.pubkey());
+        cdll(&mut client, &["pop", list]).await.unwrap();
+        let error = cdll(&mut client, &["pop", list]).await.unwrap_err();
+        assert_eq!(error.to_string(), format!("List {list} is empty"));
     }
 }